@@ -26,17 +26,134 @@
   }
 }
 
+// whether `layer` is an overlay of type `name`, in whatever version
+#let isOverlay(layer, name) = {
+  layer.type.match(regex("^spec/overlays/" + name + "(/[0-9.]+)?$")) != none
+}
 #let mappingLayer(oca) = {
-  oca.overlays.find( e => e.at(1).type == "spec/overlays/attribute_mapping/1.0" )
+  oca.overlays.find( e => isOverlay(e.at(1), "attribute_mapping") )
 }
 #let styleLayer(oca) = {
-  oca.overlays.find( e => e.at(1).type == "spec/overlays/style/1.0" )
+  oca.overlays.find( e => isOverlay(e.at(1), "style") )
 }
 #let attributeTranslation(oca, language) = {
-  oca.overlays.find(e => e.at(1).type == "spec/overlays/label/1.0")
+  json.decode(str(myplugin.label_for_language(bytes(json.encode(oca)), bytes(language))))
+}
+#let entryTranslation(oca, language) = {
+  json.decode(str(myplugin.entry_for_language(bytes(json.encode(oca)), bytes(language))))
+}
+// replaces entry codes by their label, keeping codes without one
+#let localizeEntry(entries, attr, val) = {
+  if entries == none or val == none {
+    return val
+  }
+  let labels = entries.attribute_entries.at(attr, default: none)
+  if labels == none {
+    return val
+  }
+  if type(val) == array {
+    val.map(v => labels.at(str(v), default: v))
+  } else {
+    labels.at(str(val), default: val)
+  }
+}
+#let unitLayer(oca) = {
+  oca.overlays.find(e => isOverlay(e.at(1), "unit"))
+}
+#let unitFor(oca, attr) = {
+  let layer = unitLayer(oca)
+  if layer == none { none } else { layer.at(1).attribute_units.at(attr, default: none) }
+}
+// shows a measurement in the system `units` names, or the one its locale uses
+#let formatUnit(oca, attr, val, units) = {
+  let layer = unitLayer(oca).at(1)
+  let unit = layer.attribute_units.at(attr)
+  let system = layer.at("metric_system", default: "")
+  str(myplugin.format_unit(bytes(str(val)), bytes(unit), bytes(system), bytes(units)))
+}
+// attributes flagged in the capture base or listed in a sensitive overlay
+#let sensitiveAttributes(oca) = {
+  let sensitive = oca.overlays.filter(e => isOverlay(e.at(1), "sensitive"))
+  oca.capture_base.flagged_attributes + sensitive.map(e => e.at(1).attributes).flatten()
+}
+#let redaction = sys.inputs.at("redaction", default: "none")
+#let redact(val) = {
+  if val == none {
+    return val
+  }
+  str(myplugin.redact(bytes(json.encode(val)), bytes(redaction)))
+}
+// keeps sensitive values out of the title and subtitle
+#let redactData(data, sensitive) = {
+  if redaction == "none" or type(data) != dictionary {
+    return data
+  }
+  for attr in sensitive {
+    if attr in data {
+      if redaction == "omit" {
+        let _ = data.remove(attr)
+      } else {
+        data.insert(attr, redact(data.at(attr)))
+      }
+    }
+  }
+  data
 }
 #let formatLayer(oca) = {
-  oca.overlays.find(e => e.at(1).type == "spec/overlays/format/1.0")
+  oca.overlays.find(e => isOverlay(e.at(1), "format"))
+}
+
+// the bundle a `refs:<digest>` or `Array[refs:<digest>]` attribute points to
+#let referencedBundle(oca, attrType) = {
+  let m = attrType.match(regex("^(?:Array\\[)?refs:([^\\]]+)\\]?$"))
+  if m == none {
+    return none
+  }
+  let digest = m.captures.at(0)
+  if oca.capture_base.digest == digest {
+    return oca
+  }
+  oca.at("dependencies", default: (:)).at(digest, default: none)
+}
+
+// renders the attributes of a referenced bundle with its own labels and formats
+#let nestedAttributes(data, bundle, root, language, depth) = {
+  let translation = attributeTranslation(bundle, language)
+  let entries = entryTranslation(bundle, language)
+  let formats = formatLayer(bundle)
+  let sensitive = sensitiveAttributes(bundle)
+  for (attr, attrType) in bundle.capture_base.attributes {
+    if redaction == "omit" and attr in sensitive {
+      continue
+    }
+    let val = if type(data) == dictionary { data.at(attr, default: none) } else { none }
+    let label = if translation == none { attr } else {
+      translation.at("attribute_labels").at(attr, default: attr)
+    }
+    let nested = referencedBundle(root, attrType)
+    if redaction != "none" and attr in sensitive {
+      [*#label:* #redact(val)]
+    } else if nested != none and depth < 8 {
+      // depth guards against capture bases referring to each other
+      [*#label:*]
+      for item in (if type(val) == array { val } else { (val,) }) {
+        pad(left: 1em, nestedAttributes(item, nested, root, language, depth + 1))
+      }
+    } else {
+      let dateFormat = if formats == none { none } else {
+        formats.at(1).attribute_formats.at(attr, default: none)
+      }
+      let unit = unitFor(bundle, attr)
+      if attrType == "DateTime" and dateFormat != none {
+        [*#label:* #convertDate(val, dateFormat)]
+      } else if attrType == "Numeric" and unit != none and val != none {
+        [*#label:* #formatUnit(bundle, attr, val, sys.inputs.at("units", default: language))]
+      } else {
+        [*#label:* #localizeEntry(entries, attr, val)]
+      }
+    }
+    parbreak()
+  }
 }
 
 #let resolvePath(obj, path) = {
@@ -57,7 +174,7 @@
   str(myplugin.render(bytes(text), bytes(data)))
 }
 
-#let card(data, oca) = context{
+#let card(data, oca, language) = context{
   let baseLayer = oca.capture_base
   let mapLay = mappingLayer(oca)
   let mappingLayer = if mapLay == none { none } else { mapLay.at(1)}
@@ -66,18 +183,19 @@
   } else {
     data
   }
-  let attrLayer = attributeTranslation(oca, "de")
+  let attributeTranslation = attributeTranslation(oca, language)
+  let entryTranslation = entryTranslation(oca, language)
+  let sensitive = sensitiveAttributes(oca)
   let formatLayer = formatLayer(oca).at(1)
-
-  let attributeTranslation = if attrLayer == none { none } else {
-    attrLayer.at(1)
-  }
   let style = styleLayer(oca).at(1).style_json
 
   let fontColor = if style.textColor == "light" { color.white } else { color.black }
   let backgroundColor = rgb( style.cardColor.bit-rshift(16).bit-and(255), style.cardColor.bit-rshift(8).bit-and(255) , style.cardColor.bit-and(255),style.cardColor.bit-rshift(24).bit-and(255) )
   let propertyCard(h: auto) = rect(width: 6cm, height: h, radius: 5pt, inset: 1em , stroke: black, fill: backgroundColor)[
     #for attr in style.orderedProperties {
+      if redaction == "omit" and attr in sensitive {
+        continue
+      }
 
       set text(fontColor)
       let val =  resolvePath(data, attr)
@@ -86,7 +204,17 @@
       let label = if attributeTranslation == none { attr } else {
           attributeTranslation.at("attribute_labels").at(attr, default: attr)
       }
-      if attrType == "DateTime" {
+      let nested = referencedBundle(oca, attrType)
+      if redaction != "none" and attr in sensitive {
+        [*#label:* #redact(val)]
+      } else if nested != none {
+        [*#label:*]
+        for item in (if type(val) == array { val } else { (val,) }) {
+          pad(left: 1em, nestedAttributes(item, nested, oca, language, 1))
+        }
+      } else if attrType == "Numeric" and unitFor(oca, attr) != none and val != none {
+        [*#label:* #formatUnit(oca, attr, val, sys.inputs.at("units", default: language))]
+      } else if attrType == "DateTime" {
         let dateFormat = formatLayer.attribute_formats.at(attr)
         if dateFormat == none {
            [*#label:* #val]
@@ -94,7 +222,7 @@
            [*#label:* #convertDate(val, dateFormat)]
         }
       } else {
-        [*#label:* #val]
+        [*#label:* #localizeEntry(entryTranslation, attr, val)]
       }
       parbreak()
     }
@@ -117,8 +245,8 @@
       place(image.decode(data, fit: "cover", width: 100%))
     }
     #pad(1em)[
-    = #interpolate(style.title, json.encode(data))
-    == #interpolate(style.subtitle, json.encode(data))
+    = #interpolate(style.title, json.encode(redactData(data, sensitive)))
+    == #interpolate(style.subtitle, json.encode(redactData(data, sensitive)))
     ]
   ]
   pagebreak()
@@ -128,4 +256,4 @@
 #set text(size: 8pt, font: "Noto Sans Old")
 #let oca = parseOca("style_test.oca")
 #set page(width: auto, height: auto, margin: 1pt, fill: rgb(0,0,0,0))
-#card(json.decode("{\"vc\" : { \"givenName\" : \"Manfred\", \"surname\" : \"Mustermann\", \"dateOfBirth\" : \"20001010\" }}"), oca)
+#card(json.decode("{\"vc\" : { \"givenName\" : \"Manfred\", \"surname\" : \"Mustermann\", \"dateOfBirth\" : \"20001010\" }}"), oca, sys.inputs.at("language", default: "en"))
@@ -0,0 +1,429 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    error::OcaError,
+    models::{
+        AttributeType, CaptureBase, Conformance, ConformancePolicy, Encoding, Oca, OcaLayer,
+        StyleJson,
+    },
+    overlays::Overlays,
+    said::SaidAlgorithm,
+};
+
+/// Collects attributes and overlay contents and assembles them into an [`Oca`].
+///
+/// Digests are only calculated in [`OcaBuilder::build`], capture base first, so
+/// every overlay is bound to the final capture base digest. Overlays appear in
+/// the bundle in the order they were first added to.
+#[derive(Debug, Clone, Default)]
+pub struct OcaBuilder {
+    classification: Option<String>,
+    attributes: BTreeMap<String, AttributeType>,
+    flagged_attributes: Vec<String>,
+    order: Vec<OverlayKey>,
+    meta: BTreeMap<String, (String, String)>,
+    labels: BTreeMap<String, BTreeMap<String, String>>,
+    information: BTreeMap<String, BTreeMap<String, String>>,
+    entries: BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>,
+    formats: BTreeMap<String, String>,
+    encodings: BTreeMap<String, Encoding>,
+    conformance: BTreeMap<String, ConformancePolicy>,
+    metric_system: Option<String>,
+    units: BTreeMap<String, String>,
+    cardinality: BTreeMap<String, String>,
+    standards: BTreeMap<String, String>,
+    entry_codes: BTreeMap<String, Vec<String>>,
+    sensitive: Vec<String>,
+    subset: Vec<String>,
+    style: Option<StyleJson>,
+    mapping: BTreeMap<String, String>,
+    said_algorithm: SaidAlgorithm,
+    dependencies: Vec<Oca>,
+}
+
+/// The overlays of a bundle under construction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OverlayKey {
+    Meta(String),
+    Label(String),
+    Information(String),
+    Entry(String),
+    CharacterEncoding,
+    Format,
+    Conformance,
+    Unit,
+    Cardinality,
+    Standard,
+    EntryCode,
+    Sensitive,
+    Subset,
+    AttributeMapping,
+    Style,
+}
+
+impl OverlayKey {
+    fn name(&self) -> String {
+        match self {
+            OverlayKey::Meta(language) => format!("meta ({language})"),
+            OverlayKey::Label(language) => format!("label ({language})"),
+            OverlayKey::Information(language) => format!("information ({language})"),
+            OverlayKey::Entry(language) => format!("entry ({language})"),
+            OverlayKey::CharacterEncoding => "encoding".to_string(),
+            OverlayKey::Format => "format".to_string(),
+            OverlayKey::Conformance => "conformance".to_string(),
+            OverlayKey::Unit => "unit".to_string(),
+            OverlayKey::Cardinality => "cardinality".to_string(),
+            OverlayKey::Standard => "standard".to_string(),
+            OverlayKey::EntryCode => "entry code".to_string(),
+            OverlayKey::Sensitive => "sensitive".to_string(),
+            OverlayKey::Subset => "subset".to_string(),
+            OverlayKey::AttributeMapping => "attribute mapping".to_string(),
+            OverlayKey::Style => "style".to_string(),
+        }
+    }
+}
+
+impl OcaBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn touch(&mut self, key: OverlayKey) {
+        if !self.order.contains(&key) {
+            self.order.push(key);
+        }
+    }
+    pub fn classification(mut self, classification: &str) -> Self {
+        self.classification = Some(classification.to_string());
+        self
+    }
+    pub fn add_attribute(mut self, name: &str, attribute_type: impl Into<AttributeType>) -> Self {
+        self.attributes
+            .insert(name.to_string(), attribute_type.into());
+        self
+    }
+    pub fn flag_attribute(mut self, name: &str) -> Self {
+        if !self.flagged_attributes.iter().any(|a| a == name) {
+            self.flagged_attributes.push(name.to_string());
+        }
+        self
+    }
+    /// Sets the name and description of the bundle in `language`.
+    pub fn add_meta(mut self, language: &str, name: &str, description: &str) -> Self {
+        self.meta.insert(
+            language.to_string(),
+            (name.to_string(), description.to_string()),
+        );
+        self.touch(OverlayKey::Meta(language.to_string()));
+        self
+    }
+    pub fn add_label(mut self, language: &str, attribute: &str, label: &str) -> Self {
+        self.labels
+            .entry(language.to_string())
+            .or_default()
+            .insert(attribute.to_string(), label.to_string());
+        self.touch(OverlayKey::Label(language.to_string()));
+        self
+    }
+    pub fn add_information(mut self, language: &str, attribute: &str, information: &str) -> Self {
+        self.information
+            .entry(language.to_string())
+            .or_default()
+            .insert(attribute.to_string(), information.to_string());
+        self.touch(OverlayKey::Information(language.to_string()));
+        self
+    }
+    /// Adds the label of the entry `code` of `attribute` in `language`.
+    pub fn add_entry(mut self, language: &str, attribute: &str, code: &str, label: &str) -> Self {
+        self.entries
+            .entry(language.to_string())
+            .or_default()
+            .entry(attribute.to_string())
+            .or_default()
+            .insert(code.to_string(), label.to_string());
+        self.touch(OverlayKey::Entry(language.to_string()));
+        self
+    }
+    pub fn add_format(mut self, attribute: &str, format: &str) -> Self {
+        self.formats
+            .insert(attribute.to_string(), format.to_string());
+        self.touch(OverlayKey::Format);
+        self
+    }
+    pub fn add_character_encoding(mut self, attribute: &str, encoding: Encoding) -> Self {
+        self.encodings.insert(attribute.to_string(), encoding);
+        self.touch(OverlayKey::CharacterEncoding);
+        self
+    }
+    pub fn add_conformance(mut self, attribute: &str, policy: ConformancePolicy) -> Self {
+        self.conformance.insert(attribute.to_string(), policy);
+        self.touch(OverlayKey::Conformance);
+        self
+    }
+    /// The `metric_system` of the unit overlay, `SI` by default.
+    pub fn metric_system(mut self, metric_system: &str) -> Self {
+        self.metric_system = Some(metric_system.to_string());
+        self.touch(OverlayKey::Unit);
+        self
+    }
+    pub fn add_unit(mut self, attribute: &str, unit: &str) -> Self {
+        self.units.insert(attribute.to_string(), unit.to_string());
+        self.touch(OverlayKey::Unit);
+        self
+    }
+    /// Sets the cardinality of `attribute`, e.g. `1-` or `2`.
+    pub fn add_cardinality(mut self, attribute: &str, cardinality: &str) -> Self {
+        self.cardinality
+            .insert(attribute.to_string(), cardinality.to_string());
+        self.touch(OverlayKey::Cardinality);
+        self
+    }
+    pub fn add_standard(mut self, attribute: &str, standard: &str) -> Self {
+        self.standards
+            .insert(attribute.to_string(), standard.to_string());
+        self.touch(OverlayKey::Standard);
+        self
+    }
+    /// Restricts the values of `attribute` to `codes`.
+    pub fn add_entry_codes(mut self, attribute: &str, codes: &[&str]) -> Self {
+        self.entry_codes.insert(
+            attribute.to_string(),
+            codes.iter().map(|code| code.to_string()).collect(),
+        );
+        self.touch(OverlayKey::EntryCode);
+        self
+    }
+    pub fn add_sensitive(mut self, attribute: &str) -> Self {
+        if !self.sensitive.iter().any(|a| a == attribute) {
+            self.sensitive.push(attribute.to_string());
+        }
+        self.touch(OverlayKey::Sensitive);
+        self
+    }
+    pub fn add_subset(mut self, attribute: &str) -> Self {
+        if !self.subset.iter().any(|a| a == attribute) {
+            self.subset.push(attribute.to_string());
+        }
+        self.touch(OverlayKey::Subset);
+        self
+    }
+    pub fn style(mut self, style: StyleJson) -> Self {
+        self.style = Some(style);
+        self.touch(OverlayKey::Style);
+        self
+    }
+    /// Maps `attribute` to the path `source` in the credential data.
+    pub fn add_mapping(mut self, attribute: &str, source: &str) -> Self {
+        self.mapping
+            .insert(attribute.to_string(), source.to_string());
+        self.touch(OverlayKey::AttributeMapping);
+        self
+    }
+    /// Includes `dependency`, so attributes of type `refs:<digest>` can refer to
+    /// its capture base.
+    pub fn add_dependency(mut self, dependency: Oca) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+    /// The algorithm all digests are derived with, Blake3-256 by default.
+    pub fn said_algorithm(mut self, algorithm: SaidAlgorithm) -> Self {
+        self.said_algorithm = algorithm;
+        self
+    }
+
+    /// Computes the capture base digest, then the digests of all overlays, and
+    /// checks the result with [`Oca::verify_integrity`].
+    pub fn build(mut self) -> Result<Oca, OcaError> {
+        let mut capture_base = CaptureBase::new(
+            std::mem::take(&mut self.attributes),
+            std::mem::take(&mut self.flagged_attributes),
+        );
+        capture_base.classification = self.classification.take();
+        capture_base.update_digest_with(self.said_algorithm)?;
+        let digest = capture_base.digest.clone();
+
+        let mut overlays = Overlays::new();
+        for key in std::mem::take(&mut self.order) {
+            let mut layer = match &key {
+                OverlayKey::Meta(language) => {
+                    let (name, description) = self.meta.remove(language).unwrap_or_default();
+                    OcaLayer::new_meta_layer(&digest, language, &name, &description)
+                }
+                OverlayKey::Label(language) => OcaLayer::new_label_layer(
+                    &digest,
+                    language,
+                    self.labels.remove(language).unwrap_or_default(),
+                    vec![],
+                    BTreeMap::new(),
+                ),
+                OverlayKey::Information(language) => OcaLayer::new_information_layer(
+                    &digest,
+                    language,
+                    self.information.remove(language).unwrap_or_default(),
+                ),
+                OverlayKey::Entry(language) => OcaLayer::new_entry_layer(
+                    &digest,
+                    language,
+                    self.entries.remove(language).unwrap_or_default(),
+                ),
+                OverlayKey::CharacterEncoding => {
+                    OcaLayer::new_character_encoding(&digest, std::mem::take(&mut self.encodings))
+                }
+                OverlayKey::Format => {
+                    OcaLayer::new_format_layer(&digest, std::mem::take(&mut self.formats))
+                }
+                OverlayKey::Conformance => OcaLayer::Conformance(Conformance::new(
+                    &digest,
+                    std::mem::take(&mut self.conformance),
+                )),
+                OverlayKey::Unit => OcaLayer::new_unit_layer(
+                    &digest,
+                    self.metric_system.as_deref().unwrap_or("SI"),
+                    std::mem::take(&mut self.units),
+                ),
+                OverlayKey::Cardinality => {
+                    OcaLayer::new_cardinality_layer(&digest, std::mem::take(&mut self.cardinality))
+                }
+                OverlayKey::Standard => {
+                    OcaLayer::new_standard_layer(&digest, std::mem::take(&mut self.standards))
+                }
+                OverlayKey::EntryCode => {
+                    OcaLayer::new_entry_code_layer(&digest, std::mem::take(&mut self.entry_codes))
+                }
+                OverlayKey::Sensitive => {
+                    OcaLayer::new_sensitive_layer(&digest, std::mem::take(&mut self.sensitive))
+                }
+                OverlayKey::Subset => {
+                    OcaLayer::new_subset_layer(&digest, std::mem::take(&mut self.subset))
+                }
+                OverlayKey::AttributeMapping => OcaLayer::new_attribute_mapping_layer(
+                    &digest,
+                    std::mem::take(&mut self.mapping),
+                ),
+                OverlayKey::Style => {
+                    OcaLayer::new_style_layer(&digest, self.style.take().unwrap_or_default())
+                }
+            };
+            layer.update_digest_with(self.said_algorithm)?;
+            overlays.push(key.name(), layer);
+        }
+        let mut oca = Oca {
+            capture_base,
+            overlays,
+            dependencies: BTreeMap::new(),
+        };
+        for dependency in self.dependencies {
+            oca.add_dependency(dependency);
+        }
+        oca.verify_integrity()?;
+        Ok(oca)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OcaBuilder;
+    use crate::{
+        error::OcaError,
+        models::{ConformancePolicy, Encoding, StyleJson},
+        oca::{generate_zip, parse_zip},
+        said::{Said, SaidAlgorithm},
+    };
+
+    #[test]
+    fn build_and_read_back() {
+        let oca = OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_attribute("dateOfBirth", "DateTime")
+            .add_attribute("portrait", "Binary")
+            .flag_attribute("dateOfBirth")
+            .add_label("en", "givenName", "Given name")
+            .add_label("de", "givenName", "Vorname")
+            .add_format("dateOfBirth", "%Y%m%d")
+            .add_character_encoding("portrait", Encoding::Base64)
+            .add_conformance("givenName", ConformancePolicy::M)
+            .add_mapping("givenName", "vc.givenName")
+            .style(StyleJson {
+                title: "{{ givenName }}".into(),
+                subtitle: "Test".into(),
+                card_color: 4288983525,
+                text_color: "light".into(),
+                background_card: None,
+                ordered_properties: vec!["givenName".into(), "dateOfBirth".into()],
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let oca = parse_zip(&generate_zip(oca).unwrap()).unwrap();
+
+        assert_eq!(oca.attributes().len(), 3);
+        assert!(oca.capture_base().is_flagged("dateOfBirth"));
+        assert_eq!(
+            oca.label("de").and_then(|l| l.label_for("givenName")),
+            Some("Vorname")
+        );
+        assert_eq!(oca.format_for("dateOfBirth"), Some("%Y%m%d"));
+        assert_eq!(
+            oca.character_encoding_for("portrait"),
+            Some(Encoding::Base64)
+        );
+        assert_eq!(oca.conformance_for("givenName"), Some(ConformancePolicy::M));
+        assert_eq!(oca.style().map(|s| s.subtitle.as_str()), Some("Test"));
+        assert_eq!(
+            oca.attribute_mapping()
+                .and_then(|m| m.attribute_mapping().get("givenName"))
+                .map(String::as_str),
+            Some("vc.givenName")
+        );
+        for (_, overlay) in oca.overlays() {
+            assert_eq!(overlay.capture_base(), oca.capture_base().digest());
+        }
+    }
+
+    #[test]
+    fn overlays_in_order_of_first_use() {
+        let oca = OcaBuilder::new()
+            .add_attribute("height", "Numeric")
+            .add_unit("height", "cm")
+            .add_label("en", "height", "Height")
+            .add_sensitive("height")
+            .metric_system("SI")
+            .build()
+            .unwrap();
+        let names: Vec<_> = oca
+            .overlays()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["unit", "label (en)", "sensitive"]);
+    }
+
+    #[test]
+    fn build_rejects_unknown_attributes() {
+        let result = OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_label("en", "surname", "Surname")
+            .build();
+        assert!(matches!(result, Err(OcaError::Integrity(_))));
+    }
+
+    #[test]
+    fn build_with_sha2_256() {
+        let oca = OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_label("en", "givenName", "Given name")
+            .said_algorithm(SaidAlgorithm::Sha2_256)
+            .build()
+            .unwrap();
+        let oca = parse_zip(&generate_zip(oca).unwrap()).unwrap();
+        assert!(oca.capture_base().digest().starts_with('I'));
+        for (_, overlay) in oca.overlays() {
+            assert!(overlay.digest().starts_with('I'));
+        }
+    }
+}
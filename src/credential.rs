@@ -0,0 +1,451 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Decoding of credentials into the claims the renderer and the attribute
+//! mapping work on.
+//!
+//! Compact JWT-VCs are decoded to their payload. SD-JWTs are decoded to the
+//! payload with the claims of the disclosures present filled in; the claims
+//! the holder did not disclose are listed by [`Credential::undisclosed`].
+//! Signatures are only checked if a [`SignatureVerifier`] is given.
+
+use std::collections::HashMap;
+
+use base64::Engine;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use sha3::Sha3_256;
+
+use crate::{mapping::MappingError, models::Oca};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum CredentialError {
+    #[error("malformed credential: {0}")]
+    Malformed(String),
+    #[error("invalid base64 in {part}")]
+    Base64 { part: String },
+    #[error("invalid JSON in {part}: {reason}")]
+    Json { part: String, reason: String },
+    #[error("unsupported SD-JWT digest algorithm {0}")]
+    UnsupportedDigestAlgorithm(String),
+    #[error("disclosure {0} is not referenced by the credential")]
+    UnreferencedDisclosure(String),
+    #[error("invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("invalid CBOR: {0}")]
+    Cbor(String),
+}
+
+/// Checks the signature of the issuer signed JWT, e.g. against the issuer's
+/// keys. Not part of the default build, wallets plug in their own.
+pub trait SignatureVerifier {
+    /// `signing_input` is `<header>.<payload>` as it appears in the JWT.
+    fn verify(
+        &self,
+        header: &Value,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), CredentialError>;
+}
+
+/// A claim of an SD-JWT that was not disclosed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Undisclosed {
+    /// Where the claim would be, as a dotted path to its object or array, e.g.
+    /// `vc.address` or `nationalities`. The top level is the empty path.
+    pub path: String,
+    pub digest: String,
+}
+
+/// The decoded claims of a credential.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credential {
+    header: Value,
+    claims: Value,
+    undisclosed: Vec<Undisclosed>,
+    key_binding: Option<String>,
+}
+
+impl Credential {
+    /// Decodes a compact JWT, an SD-JWT (recognized by its `~` separators) or a
+    /// plain JSON object, without checking any signature.
+    pub fn decode(input: &str) -> Result<Self, CredentialError> {
+        Self::decode_with(input, None)
+    }
+
+    /// Like [`Credential::decode`], checking the issuer signature with `verifier`.
+    pub fn decode_verified(
+        input: &str,
+        verifier: &dyn SignatureVerifier,
+    ) -> Result<Self, CredentialError> {
+        Self::decode_with(input, Some(verifier))
+    }
+
+    fn decode_with(
+        input: &str,
+        verifier: Option<&dyn SignatureVerifier>,
+    ) -> Result<Self, CredentialError> {
+        let input = input.trim();
+        if input.starts_with('{') {
+            if verifier.is_some() {
+                return Err(CredentialError::InvalidSignature(
+                    "plain JSON is not signed".to_string(),
+                ));
+            }
+            let claims = json(input.as_bytes(), "credential")?;
+            return Ok(Self {
+                header: Value::Null,
+                claims,
+                undisclosed: vec![],
+                key_binding: None,
+            });
+        }
+        let mut parts = input.split('~');
+        let jwt = parts.next().unwrap_or_default();
+        let (header, payload) = decode_jwt(jwt, verifier)?;
+        if !input.contains('~') {
+            return Ok(Self {
+                header,
+                claims: payload,
+                undisclosed: vec![],
+                key_binding: None,
+            });
+        }
+        let mut parts = parts.collect::<Vec<_>>();
+        // `~` terminates the last disclosure, anything after it is a key binding JWT
+        let key_binding = parts.pop().filter(|kb| !kb.is_empty()).map(str::to_string);
+        let algorithm = payload
+            .get("_sd_alg")
+            .and_then(Value::as_str)
+            .unwrap_or("sha-256")
+            .to_string();
+        let mut disclosures = HashMap::new();
+        for disclosure in parts.into_iter().filter(|d| !d.is_empty()) {
+            let digest = digest(&algorithm, disclosure)?;
+            let decoded = json(&base64url(disclosure, "disclosure")?, "disclosure")?;
+            disclosures.insert(digest, (disclosure.to_string(), decoded));
+        }
+        let mut reconstruction = Reconstruction {
+            disclosures,
+            undisclosed: vec![],
+        };
+        let claims = reconstruction.value(payload, "")?;
+        if let Some((disclosure, _)) = reconstruction.disclosures.into_values().next() {
+            return Err(CredentialError::UnreferencedDisclosure(disclosure));
+        }
+        Ok(Self {
+            header,
+            claims,
+            undisclosed: reconstruction.undisclosed,
+            key_binding,
+        })
+    }
+
+    /// Claims that were not decoded from a JWT, e.g. those of an mdoc.
+    pub(crate) fn from_claims(claims: Value) -> Self {
+        Self {
+            header: Value::Null,
+            claims,
+            undisclosed: Vec::new(),
+            key_binding: None,
+        }
+    }
+
+    /// The JWT header, `null` for plain JSON and mdocs.
+    pub fn header(&self) -> &Value {
+        &self.header
+    }
+    /// The claims as disclosed, to be passed to the renderer.
+    pub fn claims(&self) -> &Value {
+        &self.claims
+    }
+    pub fn into_claims(self) -> Value {
+        self.claims
+    }
+    pub fn undisclosed(&self) -> &[Undisclosed] {
+        &self.undisclosed
+    }
+    /// The key binding JWT of an SD-JWT presentation, not verified.
+    pub fn key_binding(&self) -> Option<&str> {
+        self.key_binding.as_deref()
+    }
+    /// The capture base attributes of `oca`, mapped from the claims with its
+    /// attribute mapping overlay if there is one.
+    pub fn attributes(&self, oca: &Oca) -> Result<Value, MappingError> {
+        match oca.attribute_mapping() {
+            Some(mapping) => mapping.try_map_json(&self.claims),
+            None => Ok(self.claims.clone()),
+        }
+    }
+}
+
+fn decode_jwt(
+    jwt: &str,
+    verifier: Option<&dyn SignatureVerifier>,
+) -> Result<(Value, Value), CredentialError> {
+    let [header, payload, signature] = jwt.split('.').collect::<Vec<_>>()[..] else {
+        return Err(CredentialError::Malformed(
+            "a JWT has three parts separated by dots".to_string(),
+        ));
+    };
+    let decoded_header = json(&base64url(header, "header")?, "header")?;
+    let decoded_payload = json(&base64url(payload, "payload")?, "payload")?;
+    if let Some(verifier) = verifier {
+        let signing_input = &jwt[..header.len() + 1 + payload.len()];
+        let signature = base64url(signature, "signature")?;
+        verifier.verify(&decoded_header, signing_input.as_bytes(), &signature)?;
+    }
+    Ok((decoded_header, decoded_payload))
+}
+
+fn base64url(part: &str, name: &str) -> Result<Vec<u8>, CredentialError> {
+    base64::prelude::BASE64_URL_SAFE_NO_PAD
+        .decode(part.trim_end_matches('='))
+        .map_err(|_| CredentialError::Base64 {
+            part: name.to_string(),
+        })
+}
+
+fn json(bytes: &[u8], part: &str) -> Result<Value, CredentialError> {
+    serde_json::from_slice(bytes).map_err(|e| CredentialError::Json {
+        part: part.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// The digest of a disclosure as it appears in `_sd` arrays.
+fn digest(algorithm: &str, disclosure: &str) -> Result<String, CredentialError> {
+    let input = disclosure.as_bytes();
+    let digest = match algorithm {
+        "sha-256" => Sha256::digest(input).to_vec(),
+        "sha-384" => Sha384::digest(input).to_vec(),
+        "sha-512" => Sha512::digest(input).to_vec(),
+        "sha3-256" => Sha3_256::digest(input).to_vec(),
+        _ => {
+            return Err(CredentialError::UnsupportedDigestAlgorithm(
+                algorithm.to_string(),
+            ))
+        }
+    };
+    Ok(base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(digest))
+}
+
+struct Reconstruction {
+    /// The disclosures not used yet, by digest.
+    disclosures: HashMap<String, (String, Value)>,
+    undisclosed: Vec<Undisclosed>,
+}
+
+impl Reconstruction {
+    fn value(&mut self, value: Value, path: &str) -> Result<Value, CredentialError> {
+        match value {
+            Value::Object(object) => self.object(object, path).map(Value::Object),
+            Value::Array(items) => self.array(items, path).map(Value::Array),
+            value => Ok(value),
+        }
+    }
+
+    fn object(
+        &mut self,
+        mut object: Map<String, Value>,
+        path: &str,
+    ) -> Result<Map<String, Value>, CredentialError> {
+        let digests = object.remove("_sd");
+        if path.is_empty() {
+            object.remove("_sd_alg");
+        }
+        let mut result = Map::new();
+        for (key, value) in object {
+            let value = self.value(value, &join(path, &key))?;
+            result.insert(key, value);
+        }
+        for digest in digests
+            .as_ref()
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let Some(digest) = digest.as_str() else {
+                return Err(CredentialError::Malformed(
+                    "_sd must hold strings".to_string(),
+                ));
+            };
+            let Some((disclosure, decoded)) = self.disclosures.remove(digest) else {
+                self.undisclosed.push(Undisclosed {
+                    path: path.to_string(),
+                    digest: digest.to_string(),
+                });
+                continue;
+            };
+            let Some([_salt, Value::String(name), value]) = disclosed::<3>(decoded) else {
+                return Err(CredentialError::Malformed(disclosure));
+            };
+            if name == "_sd" || name == "..." || result.contains_key(&name) {
+                return Err(CredentialError::Malformed(disclosure));
+            }
+            let value = self.value(value, &join(path, &name))?;
+            result.insert(name, value);
+        }
+        Ok(result)
+    }
+
+    fn array(&mut self, items: Vec<Value>, path: &str) -> Result<Vec<Value>, CredentialError> {
+        let mut result = vec![];
+        for item in items {
+            let digest = match &item {
+                Value::Object(object) if object.len() == 1 => object
+                    .get("...")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                _ => None,
+            };
+            let Some(digest) = digest else {
+                result.push(self.value(item, path)?);
+                continue;
+            };
+            let Some((disclosure, decoded)) = self.disclosures.remove(&digest) else {
+                self.undisclosed.push(Undisclosed {
+                    path: path.to_string(),
+                    digest,
+                });
+                continue;
+            };
+            let Some([_salt, value]) = disclosed::<2>(decoded) else {
+                return Err(CredentialError::Malformed(disclosure));
+            };
+            result.push(self.value(value, path)?);
+        }
+        Ok(result)
+    }
+}
+
+/// The parts of a disclosure, salt, name and value for object properties or
+/// salt and value for array elements.
+fn disclosed<const N: usize>(decoded: Value) -> Option<[Value; N]> {
+    match decoded {
+        Value::Array(parts) => parts.try_into().ok(),
+        _ => None,
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use serde_json::{json, Value};
+
+    use super::{digest, Credential, CredentialError, SignatureVerifier, Undisclosed};
+    use crate::builder::OcaBuilder;
+
+    fn encode(value: &Value) -> String {
+        base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(value.to_string())
+    }
+
+    fn jwt(payload: &Value) -> String {
+        let header = json!({ "alg": "ES256", "typ": "vc+sd-jwt" });
+        let signature = base64::prelude::BASE64_URL_SAFE_NO_PAD.encode("signature");
+        format!("{}.{}.{signature}", encode(&header), encode(payload))
+    }
+
+    struct Fixed;
+    impl SignatureVerifier for Fixed {
+        fn verify(
+            &self,
+            header: &Value,
+            _: &[u8],
+            signature: &[u8],
+        ) -> Result<(), CredentialError> {
+            if header["alg"] == "ES256" && signature == b"signature" {
+                Ok(())
+            } else {
+                Err(CredentialError::InvalidSignature("unexpected".into()))
+            }
+        }
+    }
+
+    #[test]
+    fn jwt_vc() {
+        let payload = json!({ "iss": "did:example:issuer", "vc": { "givenName": "Erika" } });
+        let credential = Credential::decode(&jwt(&payload)).unwrap();
+        assert_eq!(credential.claims(), &payload);
+        assert!(Credential::decode_verified(&jwt(&payload), &Fixed).is_ok());
+        assert!(matches!(
+            Credential::decode("a.b"),
+            Err(CredentialError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn sd_jwt() {
+        let given_name = encode(&json!(["salt1", "givenName", "Erika"]));
+        let surname = encode(&json!(["salt2", "surname", "Mustermann"]));
+        let nationality = encode(&json!(["salt3", "CH"]));
+        let hidden = encode(&json!(["salt4", "DE"]));
+        let payload = json!({
+            "iss": "did:example:issuer",
+            "_sd_alg": "sha-256",
+            "_sd": [digest("sha-256", &given_name).unwrap(), digest("sha-256", &surname).unwrap()],
+            "nationalities": [
+                { "...": digest("sha-256", &nationality).unwrap() },
+                { "...": digest("sha-256", &hidden).unwrap() }
+            ]
+        });
+        let presentation = format!("{}~{given_name}~{nationality}~", jwt(&payload));
+        let credential = Credential::decode(&presentation).unwrap();
+        assert_eq!(
+            credential.claims(),
+            &json!({
+                "iss": "did:example:issuer",
+                "nationalities": ["CH"],
+                "givenName": "Erika"
+            })
+        );
+        assert_eq!(
+            credential.undisclosed(),
+            [
+                Undisclosed {
+                    path: "nationalities".into(),
+                    digest: digest("sha-256", &hidden).unwrap()
+                },
+                Undisclosed {
+                    path: "".into(),
+                    digest: digest("sha-256", &surname).unwrap()
+                },
+            ]
+        );
+        assert_eq!(credential.key_binding(), None);
+
+        let oca = OcaBuilder::new()
+            .add_attribute("firstName", "Text")
+            .add_mapping("firstName", "givenName")
+            .build()
+            .unwrap();
+        assert_eq!(
+            credential.attributes(&oca).unwrap(),
+            json!({ "firstName": "Erika" })
+        );
+        let broken = OcaBuilder::new()
+            .add_attribute("firstName", "Text")
+            .add_mapping("firstName", "concat(givenName")
+            .build()
+            .unwrap();
+        assert!(credential.attributes(&broken).is_err());
+
+        let foreign = encode(&json!(["salt5", "age", 60]));
+        let tampered = format!("{}~{given_name}~{foreign}~", jwt(&payload));
+        assert!(matches!(
+            Credential::decode(&tampered),
+            Err(CredentialError::UnreferencedDisclosure(_))
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// Errors produced while reading, writing or converting OCA bundles.
+#[derive(Debug, thiserror::Error)]
+pub enum OcaError {
+    #[error("invalid OCA archive: {0}")]
+    InvalidArchive(#[from] zip::result::ZipError),
+    #[error("meta.json is missing from the archive")]
+    MissingMeta,
+    #[error("meta.json does not name a root capture base")]
+    MissingRoot,
+    #[error("meta.json is malformed: {0}")]
+    InvalidMeta(String),
+    #[error("JSON bundle is malformed: {0}")]
+    InvalidBundle(String),
+    #[error("layer file {0} is missing from the archive")]
+    MissingLayerFile(String),
+    #[error("SAID mismatch: expected {expected}, calculated {actual}")]
+    SaidMismatch { expected: String, actual: String },
+    #[error("unknown SAID derivation code in {0}")]
+    UnknownDerivationCode(String),
+    #[error("inconsistent bundle: {0}")]
+    Inconsistent(crate::oca::ParseWarning),
+    #[error(
+        "bundle failed integrity checks: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    Integrity(Vec<crate::integrity::IntegrityViolation>),
+    #[error(transparent)]
+    Credential(#[from] crate::credential::CredentialError),
+    #[error(transparent)]
+    Mapping(#[from] crate::mapping::MappingError),
+    #[error("invalid OCAfile at {0}")]
+    OcaFile(#[from] crate::ocafile::OcaFileError),
+    #[error("failed to decode {file}: {source}")]
+    Json {
+        file: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("failed to serialize: {0}")]
+    Serialization(#[source] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("failed to fetch {url}: {reason}")]
+    Fetch { url: String, reason: String },
+}
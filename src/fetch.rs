@@ -0,0 +1,384 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::OcaError;
+
+/// A request for the document at `url`, conditional if validators of a cached
+/// copy are given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchRequest {
+    pub url: String,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<String>,
+}
+
+impl FetchRequest {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchResponse {
+    /// The document, with the validators to revalidate it later.
+    Fetched {
+        body: String,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    /// The document matches the validators of the request.
+    NotModified,
+}
+
+/// Retrieves style JSON documents, e.g. over HTTP with [`UreqFetcher`] or from a
+/// local mirror with [`FileFetcher`].
+pub trait StyleFetcher {
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, OcaError>;
+
+    /// Fetches `url` unconditionally and returns its body.
+    fn fetch_string(&self, url: &str) -> Result<String, OcaError> {
+        match self.fetch(&FetchRequest::new(url))? {
+            FetchResponse::Fetched { body, .. } => Ok(body),
+            FetchResponse::NotModified => Err(OcaError::Fetch {
+                url: url.to_string(),
+                reason: "unexpected 304 Not Modified".to_string(),
+            }),
+        }
+    }
+}
+
+impl<T: StyleFetcher + ?Sized> StyleFetcher for &T {
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, OcaError> {
+        (**self).fetch(request)
+    }
+}
+
+/// Fetches over HTTP with [`ureq`].
+#[cfg(feature = "ureq")]
+pub struct UreqFetcher {
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "ureq")]
+impl UreqFetcher {
+    /// A fetcher giving up on connecting after `connect` and on each read
+    /// after `read`.
+    pub fn with_timeouts(connect: std::time::Duration, read: std::time::Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(connect)
+                .timeout_read(read)
+                .build(),
+        }
+    }
+    pub fn with_agent(agent: ureq::Agent) -> Self {
+        Self { agent }
+    }
+}
+
+/// Times out after 10 seconds connecting or 30 seconds reading.
+#[cfg(feature = "ureq")]
+impl Default for UreqFetcher {
+    fn default() -> Self {
+        Self::with_timeouts(
+            std::time::Duration::from_secs(10),
+            std::time::Duration::from_secs(30),
+        )
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl StyleFetcher for UreqFetcher {
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, OcaError> {
+        let fetch_error = |reason: String| OcaError::Fetch {
+            url: request.url.clone(),
+            reason,
+        };
+        let mut call = self.agent.get(&request.url);
+        if let Some(etag) = &request.if_none_match {
+            call = call.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &request.if_modified_since {
+            call = call.set("If-Modified-Since", last_modified);
+        }
+        let response = call.call().map_err(|e| fetch_error(format!("{e}")))?;
+        if response.status() == 304 {
+            return Ok(FetchResponse::NotModified);
+        }
+        let etag = response.header("ETag").map(str::to_string);
+        let last_modified = response.header("Last-Modified").map(str::to_string);
+        let body = response
+            .into_string()
+            .map_err(|e| fetch_error(format!("{e}")))?;
+        Ok(FetchResponse::Fetched {
+            body,
+            etag,
+            last_modified,
+        })
+    }
+}
+
+/// Reads documents from a directory mirroring the servers, so
+/// `https://example.com/v1/style` is read from `<root>/example.com/v1/style`.
+/// `file://` URLs are resolved below the root as well, `file:///styles/id.json`
+/// is read from `<root>/styles/id.json`.
+pub struct FileFetcher {
+    root: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+    /// The file `url` is read from, if it is one this fetcher serves.
+    pub fn path_for(&self, url: &str) -> Option<PathBuf> {
+        let (_, rest) = url.split_once("://")?;
+        let rest = rest.split(['?', '#']).next().unwrap_or_default();
+        let mut path = self.root.clone();
+        for segment in rest.split('/').filter(|s| !s.is_empty()) {
+            // never leave the mirror
+            if segment == ".." || segment == "." {
+                return None;
+            }
+            path.push(segment);
+        }
+        Some(path)
+    }
+}
+
+impl StyleFetcher for FileFetcher {
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, OcaError> {
+        let path = self.path_for(&request.url).ok_or_else(|| OcaError::Fetch {
+            url: request.url.clone(),
+            reason: "not a URL".to_string(),
+        })?;
+        let body = std::fs::read_to_string(&path).map_err(|e| OcaError::Fetch {
+            url: request.url.clone(),
+            reason: format!("{}: {e}", path.display()),
+        })?;
+        Ok(FetchResponse::Fetched {
+            body,
+            etag: None,
+            last_modified: None,
+        })
+    }
+}
+
+/// Serves documents from memory, for tests. Every document gets an ETag derived
+/// from its content, and every request is recorded.
+#[derive(Default)]
+pub struct MemoryFetcher {
+    documents: HashMap<String, String>,
+    requests: Mutex<Vec<FetchRequest>>,
+}
+
+impl MemoryFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_document(mut self, url: &str, body: &str) -> Self {
+        self.insert(url, body);
+        self
+    }
+    pub fn insert(&mut self, url: &str, body: &str) {
+        self.documents.insert(url.to_string(), body.to_string());
+    }
+    pub fn remove(&mut self, url: &str) {
+        self.documents.remove(url);
+    }
+    /// The requests made so far.
+    pub fn requests(&self) -> Vec<FetchRequest> {
+        self.requests.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+}
+
+impl StyleFetcher for MemoryFetcher {
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, OcaError> {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(request.clone());
+        }
+        let body = self
+            .documents
+            .get(&request.url)
+            .ok_or_else(|| OcaError::Fetch {
+                url: request.url.clone(),
+                reason: "404 Not Found".to_string(),
+            })?;
+        let etag = format!("\"{}\"", blake3::hash(body.as_bytes()).to_hex());
+        if request.if_none_match.as_ref() == Some(&etag) {
+            return Ok(FetchResponse::NotModified);
+        }
+        Ok(FetchResponse::Fetched {
+            body: body.clone(),
+            etag: Some(etag),
+            last_modified: None,
+        })
+    }
+}
+
+/// What is stored next to a cached document.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Caches the documents `inner` fetches in a directory. Cached documents are
+/// revalidated with their `ETag` or `Last-Modified` and served as they are if
+/// the document is unchanged or `inner` fails, e.g. because there is no network.
+pub struct CachingFetcher<F> {
+    inner: F,
+    directory: PathBuf,
+}
+
+impl<F: StyleFetcher> CachingFetcher<F> {
+    pub fn new(inner: F, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            directory: directory.into(),
+        }
+    }
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = blake3::hash(url.as_bytes()).to_hex();
+        (
+            self.directory.join(format!("{key}.json")),
+            self.directory.join(format!("{key}.meta.json")),
+        )
+    }
+    fn cached(&self, url: &str) -> Option<(String, CacheEntry)> {
+        let (body_path, entry_path) = self.paths(url);
+        let body = std::fs::read_to_string(body_path).ok()?;
+        let entry = std::fs::read(entry_path).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&entry).ok()?;
+        (entry.url == url).then_some((body, entry))
+    }
+    fn store(&self, entry: &CacheEntry, body: &str) -> Result<(), OcaError> {
+        let (body_path, entry_path) = self.paths(&entry.url);
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(body_path, body)?;
+        let entry = serde_json::to_vec(entry).map_err(OcaError::Serialization)?;
+        std::fs::write(entry_path, entry)?;
+        Ok(())
+    }
+}
+
+impl<F: StyleFetcher> StyleFetcher for CachingFetcher<F> {
+    fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse, OcaError> {
+        let cached = self.cached(&request.url);
+        let mut conditional = request.clone();
+        if let Some((_, entry)) = &cached {
+            conditional.if_none_match = entry.etag.clone();
+            conditional.if_modified_since = entry.last_modified.clone();
+        }
+        let response = match (self.inner.fetch(&conditional), cached) {
+            (Ok(FetchResponse::NotModified), Some((body, entry))) => FetchResponse::Fetched {
+                body,
+                etag: entry.etag,
+                last_modified: entry.last_modified,
+            },
+            (
+                Ok(FetchResponse::Fetched {
+                    body,
+                    etag,
+                    last_modified,
+                }),
+                _,
+            ) => {
+                let entry = CacheEntry {
+                    url: request.url.clone(),
+                    etag,
+                    last_modified,
+                };
+                self.store(&entry, &body)?;
+                FetchResponse::Fetched {
+                    body,
+                    etag: entry.etag,
+                    last_modified: entry.last_modified,
+                }
+            }
+            (Err(_), Some((body, entry))) => FetchResponse::Fetched {
+                body,
+                etag: entry.etag,
+                last_modified: entry.last_modified,
+            },
+            (result, None) => return result,
+        };
+        // the caller's own validators still apply
+        match &response {
+            FetchResponse::Fetched { etag, .. }
+                if etag.is_some() && request.if_none_match == *etag =>
+            {
+                Ok(FetchResponse::NotModified)
+            }
+            _ => Ok(response),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachingFetcher, FetchRequest, FileFetcher, MemoryFetcher, StyleFetcher};
+
+    const URL: &str = "https://example.com/v1/schema/Basis%20ID/0.0.1";
+
+    #[test]
+    fn caching_revalidates_and_works_offline() {
+        let directory = std::env::temp_dir().join(format!(
+            "oca-render-cache-{}",
+            blake3::hash(format!("{:?}", std::time::SystemTime::now()).as_bytes()).to_hex()
+        ));
+        let memory = MemoryFetcher::new().with_document(URL, "{}");
+        let fetcher = CachingFetcher::new(&memory, &directory);
+        assert_eq!(fetcher.fetch_string(URL).unwrap(), "{}");
+        assert_eq!(fetcher.fetch_string(URL).unwrap(), "{}");
+        let requests = memory.requests();
+        assert_eq!(requests[0].if_none_match, None);
+        assert!(requests[1].if_none_match.is_some());
+
+        // the network is gone, the cached copy is served
+        let offline = CachingFetcher::new(MemoryFetcher::new(), &directory);
+        assert_eq!(offline.fetch_string(URL).unwrap(), "{}");
+        let uncached = FetchRequest::new("https://example.com/other");
+        assert!(offline.fetch(&uncached).is_err());
+
+        // changed documents replace the cached copy
+        let changed = MemoryFetcher::new().with_document(URL, "[]");
+        let fetcher = CachingFetcher::new(changed, &directory);
+        assert_eq!(fetcher.fetch_string(URL).unwrap(), "[]");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn file_paths() {
+        let fetcher = FileFetcher::new("/mirror");
+        assert_eq!(
+            fetcher.path_for(URL).unwrap(),
+            std::path::Path::new("/mirror/example.com/v1/schema/Basis%20ID/0.0.1")
+        );
+        assert_eq!(
+            fetcher.path_for("file:///styles/style.json").unwrap(),
+            std::path::Path::new("/mirror/styles/style.json")
+        );
+        assert_eq!(fetcher.path_for("https://example.com/../etc/passwd"), None);
+        assert_eq!(fetcher.path_for("file:///../etc/passwd"), None);
+        assert_eq!(fetcher.path_for("file://../../etc/passwd"), None);
+    }
+}
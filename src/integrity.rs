@@ -0,0 +1,145 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+
+use crate::{error::OcaError, models::Oca, overlays::unversioned_type};
+
+/// A consistency problem between the overlays of an [`Oca`] and its capture base.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityViolation {
+    /// The overlay is bound to a different capture base.
+    CaptureBaseMismatch {
+        overlay: String,
+        expected: String,
+        actual: String,
+    },
+    /// Another overlay of the same type and language precedes this one.
+    DuplicateOverlay {
+        overlay: String,
+        overlay_type: String,
+        language: Option<String>,
+    },
+    /// The overlay refers to an attribute the capture base does not define.
+    UnknownAttribute { overlay: String, attribute: String },
+    /// A `refs:` attribute names a capture base that is not part of the bundle.
+    UnresolvedReference {
+        capture_base: String,
+        attribute: String,
+        reference: String,
+    },
+}
+
+impl Display for IntegrityViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityViolation::CaptureBaseMismatch {
+                overlay,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "overlay {overlay} belongs to capture base {actual} instead of {expected}"
+            ),
+            IntegrityViolation::DuplicateOverlay {
+                overlay,
+                overlay_type,
+                language: Some(language),
+            } => write!(
+                f,
+                "overlay {overlay} duplicates {overlay_type} ({language})"
+            ),
+            IntegrityViolation::DuplicateOverlay {
+                overlay,
+                overlay_type,
+                language: None,
+            } => write!(f, "overlay {overlay} duplicates {overlay_type}"),
+            IntegrityViolation::UnknownAttribute { overlay, attribute } => {
+                write!(
+                    f,
+                    "overlay {overlay} refers to unknown attribute {attribute}"
+                )
+            }
+            IntegrityViolation::UnresolvedReference {
+                capture_base,
+                attribute,
+                reference,
+            } => write!(
+                f,
+                "attribute {attribute} of {capture_base} refers to missing capture base {reference}"
+            ),
+        }
+    }
+}
+
+impl Oca {
+    /// Checks that all overlays belong to the capture base, that no overlay type
+    /// appears twice for the same language and that overlays only refer to
+    /// attributes of the capture base, for this bundle and its dependencies.
+    /// Also checks that every `refs:` attribute can be resolved.
+    pub fn verify_integrity(&self) -> Result<(), OcaError> {
+        let violations = self.integrity_violations();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(OcaError::Integrity(violations))
+        }
+    }
+
+    pub fn integrity_violations(&self) -> Vec<IntegrityViolation> {
+        let mut violations = vec![];
+        for bundle in std::iter::once(self).chain(self.dependencies.values()) {
+            violations.extend(bundle.overlay_violations());
+            for (attribute, attribute_type) in bundle.attributes() {
+                let Some(reference) = attribute_type.reference() else {
+                    continue;
+                };
+                if self.bundle(reference).is_none() {
+                    violations.push(IntegrityViolation::UnresolvedReference {
+                        capture_base: bundle.capture_base.digest.clone(),
+                        attribute: attribute.clone(),
+                        reference: reference.to_string(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    fn overlay_violations(&self) -> Vec<IntegrityViolation> {
+        let mut violations = vec![];
+        let root = &self.capture_base.digest;
+        let overlays = self.overlays.as_slice();
+        for (index, (name, layer)) in overlays.iter().enumerate() {
+            if layer.capture_base() != root {
+                violations.push(IntegrityViolation::CaptureBaseMismatch {
+                    overlay: name.clone(),
+                    expected: root.clone(),
+                    actual: layer.capture_base().to_string(),
+                });
+            }
+            if overlays[..index].iter().any(|(_, other)| {
+                unversioned_type(other.overlay_type()) == unversioned_type(layer.overlay_type())
+                    && other.language() == layer.language()
+            }) {
+                violations.push(IntegrityViolation::DuplicateOverlay {
+                    overlay: name.clone(),
+                    overlay_type: layer.overlay_type().to_string(),
+                    language: layer.language().map(str::to_string),
+                });
+            }
+            for attribute in layer.attribute_names() {
+                if !self.capture_base.attributes.contains_key(attribute) {
+                    violations.push(IntegrityViolation::UnknownAttribute {
+                        overlay: name.clone(),
+                        attribute: attribute.to_string(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
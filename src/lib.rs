@@ -1,26 +1,68 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use models::{AttributeFieldType, CaptureBase, Encoding, Oca, OcaLayer, StyleJsonFile};
+use error::OcaError;
+use fetch::StyleFetcher;
+use models::{
+    Attribute, AttributeFieldType, AttributeType, CaptureBase, Encoding, Format, Label, Meta, Oca,
+    OcaLayer, StyleJson, StyleJsonFile, StyleTranslation,
+};
 
+pub mod builder;
+pub mod credential;
+pub mod error;
+pub mod fetch;
+pub mod integrity;
+pub mod mapping;
+pub mod mdoc;
 pub mod models;
 pub mod oca;
+pub mod ocafile;
+pub mod overlays;
+pub mod redaction;
 pub mod said;
 #[cfg(feature = "typst-plugin")]
 pub mod typst;
 #[cfg(feature = "typst-renderer")]
 pub mod typst_renderer;
+pub mod units;
+pub mod validation;
 
+/// Fetches the style JSON at `url` over HTTP and converts it with
+/// [`oca_from_style_json`].
 #[cfg(feature = "ureq")]
-pub fn oca_from_style(url: &str) -> Result<Oca, String> {
-    let style = ureq::get(url).call().map_err(|e| format!("{e}"))?;
-    let style_json = style
-        .into_json::<StyleJsonFile>()
-        .map_err(|e| format!("{e}"))?;
+pub fn oca_from_style(url: &str) -> Result<Oca, OcaError> {
+    oca_from_style_with(url, &fetch::UreqFetcher::default())
+}
+
+/// Like [`oca_from_style`], retrieving the style JSON with `fetcher`.
+pub fn oca_from_style_with(url: &str, fetcher: &impl StyleFetcher) -> Result<Oca, OcaError> {
+    let body = fetcher.fetch_string(url)?;
+    let style_json =
+        serde_json::from_str::<StyleJsonFile>(&body).map_err(|source| OcaError::Json {
+            file: url.to_string(),
+            source,
+        })?;
     oca_from_style_json(style_json)
 }
 
-pub fn oca_from_style_json(style_json: StyleJsonFile) -> Result<Oca, String> {
-    let mut attributes = BTreeMap::<String, String>::new();
+/// The format overlay field listing the style JSON field types that cannot be
+/// told from the attribute type and format, i.e. `DATEOFBIRTH`.
+const FIELD_TYPES: &str = "field_types";
+
+/// Converts a schema creator style JSON into a bundle.
+///
+/// There is a label overlay for each language of the attributes' display
+/// names, plus an English one with the plain display names unless they are
+/// translated to English, and a meta overlay with title and subtitle for each
+/// translation of the style.
+pub fn oca_from_style_json(style_json: StyleJsonFile) -> Result<Oca, OcaError> {
+    let mut languages = style_json
+        .attributes
+        .values()
+        .flat_map(|attribute| attribute.display_names.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    languages.insert("en".to_string());
+    let mut attributes = BTreeMap::<String, AttributeType>::new();
     let mut vc_attributes = style_json.attributes.into_iter().collect::<Vec<(_, _)>>();
     vc_attributes.sort_by(|a, b| {
         style_json
@@ -36,17 +78,21 @@ pub fn oca_from_style_json(style_json: StyleJsonFile) -> Result<Oca, String> {
                     .position(|c| c == &b.0),
             )
     });
-    let mut attr_desc = BTreeMap::<String, String>::new();
+    let mut attr_desc = BTreeMap::<String, BTreeMap<String, String>>::new();
     let mut format_desc = BTreeMap::<String, String>::new();
     let mut attr_enc = BTreeMap::<String, Encoding>::new();
+    let mut field_types = serde_json::Map::new();
 
     for (key, value) in &vc_attributes {
-        let ty: String = match value.field_type {
+        let ty = match value.field_type {
             AttributeFieldType::Date
             | AttributeFieldType::DateOfBirth
             | AttributeFieldType::DateTime
-            | AttributeFieldType::Time => "DateTime".into(),
-            _ => "Text".into(),
+            | AttributeFieldType::Time => AttributeType::DateTime,
+            AttributeFieldType::Image => AttributeType::Binary,
+            AttributeFieldType::Boolean => AttributeType::Boolean,
+            AttributeFieldType::Number => AttributeType::Numeric,
+            AttributeFieldType::String | AttributeFieldType::Other => AttributeType::Text,
         };
         let format: Option<String> = match value.field_type {
             AttributeFieldType::Date | AttributeFieldType::DateOfBirth => Some("%Y%m%d".into()),
@@ -61,39 +107,187 @@ pub fn oca_from_style_json(style_json: StyleJsonFile) -> Result<Oca, String> {
                 attr_enc.insert(key.into(), Encoding::Base64);
             }
         }
+        if let AttributeFieldType::DateOfBirth = value.field_type {
+            let field_type =
+                serde_json::to_value(value.field_type).map_err(OcaError::Serialization)?;
+            field_types.insert(key.into(), field_type);
+        }
         attributes.insert(key.into(), ty);
-        attr_desc.insert(key.into(), value.display_name.to_string());
+        for language in &languages {
+            attr_desc
+                .entry(language.clone())
+                .or_default()
+                .insert(key.into(), value.display_name_for(language).to_string());
+        }
     }
     let mut capture_base = CaptureBase::new(attributes, vec![]);
-    capture_base.update_digest().unwrap();
+    capture_base.update_digest()?;
     let capture_base_digest = capture_base.digest.clone();
-    let label_layer = OcaLayer::new_label_layer(
-        &capture_base_digest,
-        "en",
-        attr_desc,
-        vec![],
-        BTreeMap::new(),
-    );
+    let mut overlays = attr_desc
+        .into_iter()
+        .map(|(language, labels)| {
+            let label_layer = OcaLayer::new_label_layer(
+                &capture_base_digest,
+                &language,
+                labels,
+                vec![],
+                BTreeMap::new(),
+            );
+            (format!("label ({language})"), label_layer)
+        })
+        .collect::<Vec<_>>();
+    let meta_layers = style_json
+        .translations
+        .iter()
+        .map(|(language, translation)| {
+            let name = translation
+                .title
+                .as_ref()
+                .unwrap_or(&style_json.style.title);
+            let description = translation
+                .subtitle
+                .as_ref()
+                .unwrap_or(&style_json.style.subtitle);
+            let meta_layer =
+                OcaLayer::new_meta_layer(&capture_base_digest, language, name, description);
+            (format!("meta ({language})"), meta_layer)
+        })
+        .collect::<Vec<_>>();
     let style_layer = OcaLayer::new_style_layer(&capture_base_digest, style_json.style);
 
-    let format_layer = OcaLayer::new_format_layer(&capture_base_digest, format_desc);
+    let mut format_layer = OcaLayer::new_format_layer(&capture_base_digest, format_desc);
+    if let OcaLayer::Format(format) = &mut format_layer {
+        if !field_types.is_empty() {
+            format
+                .extra
+                .insert(FIELD_TYPES.to_string(), field_types.into());
+        }
+    }
     let encoding_layer = OcaLayer::new_character_encoding(&capture_base_digest, attr_enc);
+    overlays.extend([
+        ("style".into(), style_layer),
+        ("format".into(), format_layer),
+        ("encoding".into(), encoding_layer),
+    ]);
+    overlays.extend(meta_layers);
     Ok(Oca {
         capture_base,
-        overlays: vec![
-            ("label (en)".into(), label_layer),
-            ("style".into(), style_layer),
-            ("format".into(), format_layer),
-            ("encoding".into(), encoding_layer),
-        ],
+        overlays: overlays.into(),
+        dependencies: BTreeMap::new(),
     })
 }
 
+/// Converts `oca` back into a schema creator style JSON, with the display names
+/// of the label overlay best matching `language`, the ones of all label
+/// overlays by language, and the meta overlays as translations of the style.
+///
+/// Field types follow from the capture base types and, for dates and times,
+/// the format overlay, except for `DATEOFBIRTH`, which the format overlay
+/// records separately. Bundles without style overlay get a default style listing all
+/// attributes.
+pub fn style_json_from_oca(oca: &Oca, language: &str) -> StyleJsonFile {
+    let labels = oca.label_for_language(language);
+    let recorded_types = oca
+        .overlays()
+        .overlays_of::<Format>()
+        .find_map(|format| format.extra.get(FIELD_TYPES))
+        .and_then(|types| {
+            serde_json::from_value::<BTreeMap<String, AttributeFieldType>>(types.clone()).ok()
+        })
+        .unwrap_or_default();
+    let all_labels = oca.overlays().overlays_of::<Label>().collect::<Vec<_>>();
+    // a lone English label overlay is what plain display names turn into
+    let multilingual = match all_labels.as_slice() {
+        [] => false,
+        [label] => label.language() != "en",
+        _ => true,
+    };
+    let attributes = oca
+        .attributes()
+        .iter()
+        .map(|(name, attribute_type)| {
+            let display_name = labels
+                .and_then(|labels| labels.label_for(name))
+                .unwrap_or(name)
+                .to_string();
+            let field_type = recorded_types
+                .get(name)
+                .copied()
+                .unwrap_or_else(|| field_type(attribute_type, oca.format_for(name)));
+            let display_names = all_labels
+                .iter()
+                .filter(|_| multilingual)
+                .filter_map(|label| {
+                    let display_name = label.label_for(name)?;
+                    Some((label.language().to_string(), display_name.to_string()))
+                })
+                .collect();
+            (
+                name.clone(),
+                Attribute {
+                    display_name,
+                    field_type,
+                    display_names,
+                },
+            )
+        })
+        .collect();
+    let style = oca.style().cloned().unwrap_or_else(|| StyleJson {
+        ordered_properties: oca.attributes().keys().cloned().collect(),
+        ..Default::default()
+    });
+    let translations = oca
+        .overlays()
+        .overlays_of::<Meta>()
+        .map(|meta| {
+            let translation = StyleTranslation {
+                title: Some(meta.name().to_string()),
+                subtitle: Some(meta.description().to_string()),
+            };
+            (meta.language().to_string(), translation)
+        })
+        .collect();
+    StyleJsonFile {
+        attributes,
+        style,
+        translations,
+    }
+}
+
+fn field_type(attribute_type: &AttributeType, format: Option<&str>) -> AttributeFieldType {
+    match attribute_type {
+        AttributeType::Text => AttributeFieldType::String,
+        AttributeType::Numeric => AttributeFieldType::Number,
+        AttributeType::Boolean => AttributeFieldType::Boolean,
+        AttributeType::Binary => AttributeFieldType::Image,
+        AttributeType::DateTime => {
+            let format = format.unwrap_or_default();
+            let has_date = ["%Y", "%m", "%d", "%F"].iter().any(|s| format.contains(s));
+            let has_time = ["%H", "%M", "%S", "%T"].iter().any(|s| format.contains(s));
+            match (has_date, has_time) {
+                (true, false) => AttributeFieldType::Date,
+                (false, true) => AttributeFieldType::Time,
+                _ => AttributeFieldType::DateTime,
+            }
+        }
+        AttributeType::Reference(_) | AttributeType::Array(_) | AttributeType::Other(_) => {
+            AttributeFieldType::Other
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use models::{Conformance, StyleJson};
-    use oca::{generate_zip, parse_zip};
+    use base64::Engine;
+    use integrity::IntegrityViolation;
+    use models::{Conformance, Entry, Meta, Sensitive, StyleJson, Subset, Unit};
+    use oca::{
+        generate_json_bundle, generate_zip, generate_zip_with_options, parse_bundle,
+        parse_bundle_with_options, parse_zip, parse_zip_with_options, GenerateOptions,
+        ParseOptions, ParseWarning, SkipReason,
+    };
     use said::{verify_said_from_str, Said};
+    use serde_json::Value;
 
     use super::*;
 
@@ -115,13 +309,29 @@ mod tests {
     #[test]
     fn get_oca_from_style_json() {
         let url = "https://dev-ssi-schema-creator-ws.ubique.ch/v1/schema/Basis%20ID/0.0.1";
-        let oca = oca_from_style(url).unwrap();
-        let zip = generate_zip(oca).unwrap();
-        std::fs::write("basis_id.oca", zip).unwrap();
+        let style = serde_json::json!({
+            "attributes": {
+                "givenName": { "displayName": "Vorname", "fieldType": "STRING" },
+                "dateOfBirth": { "displayName": "Geburtsdatum", "fieldType": "DATEOFBIRTH" }
+            },
+            "style": {
+                "title": "Basis ID", "subtitle": "{{ givenName }}", "cardColor": 4288983525u64,
+                "textColor": "light", "backgroundCard": null,
+                "orderedProperties": ["givenName", "dateOfBirth"]
+            }
+        });
+        let fetcher = fetch::MemoryFetcher::new().with_document(url, &style.to_string());
+        let oca = oca_from_style_with(url, &fetcher).unwrap();
+        let oca = parse_zip(&generate_zip(oca).unwrap()).unwrap();
+        assert_eq!(oca.format_for("dateOfBirth"), Some("%Y%m%d"));
+        assert_eq!(oca.style().map(|s| s.title.as_str()), Some("Basis ID"));
+
+        let missing = oca_from_style_with("https://example.com/missing", &fetcher);
+        assert!(matches!(missing, Err(OcaError::Fetch { .. })));
     }
     #[test]
     fn generate_custom() {
-        let mut attributes = BTreeMap::<String, String>::new();
+        let mut attributes = BTreeMap::<String, AttributeType>::new();
         attributes.insert("givenName".to_string(), "Text".into());
         attributes.insert("surname".to_string(), "Text".into());
         attributes.insert("dateOfBirth".to_string(), "DateTime".into());
@@ -143,6 +353,7 @@ mod tests {
             text_color: "light".into(),
             background_card: None,
             ordered_properties: vec!["givenName".into(), "surname".into(), "dateOfBirth".into()],
+            ..Default::default()
         };
 
         let style_layer = OcaLayer::new_style_layer(&capture_base_digest, style_json);
@@ -171,12 +382,636 @@ mod tests {
                 ("layer (en)".into(), label_layer),
                 ("format".into(), format_layer),
                 ("style".into(), style_layer),
-            ],
+            ]
+            .into(),
+            dependencies: BTreeMap::new(),
         };
         let zip = generate_zip(oca).unwrap();
         std::fs::write("style_test.oca", zip).unwrap();
     }
 
+    fn zip_from_files(files: &[(&str, &str)]) -> Vec<u8> {
+        use std::io::Write;
+        let mut buffer = vec![];
+        {
+            let mut archive = zip::ZipWriter::new(std::io::Cursor::new(&mut buffer));
+            for (name, content) in files {
+                archive
+                    .start_file(*name, zip::write::SimpleFileOptions::default())
+                    .unwrap();
+                archive.write_all(content.as_bytes()).unwrap();
+            }
+            archive.finish().unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn parse_zip_errors() {
+        assert!(matches!(
+            parse_zip(b"not a zip"),
+            Err(OcaError::InvalidArchive(_))
+        ));
+        assert!(matches!(
+            parse_zip(&zip_from_files(&[("other.json", "{}")])),
+            Err(OcaError::MissingMeta)
+        ));
+        assert!(matches!(
+            parse_zip(&zip_from_files(&[("meta.json", "{\"files\": {}}")])),
+            Err(OcaError::MissingRoot)
+        ));
+        let root = "EXy5B1zSvmJwiGZUEgbdrpNM4FeAjXYPa2d4HrBd5Ac4";
+        let meta = format!("{{\"root\": \"{root}\", \"files\": {{}}}}");
+        assert!(matches!(
+            parse_zip(&zip_from_files(&[("meta.json", &meta)])),
+            Err(OcaError::MissingLayerFile(name)) if name == format!("{root}.json")
+        ));
+        let capture_base = format!("{{\"digest\": \"{root}\"}}");
+        let Err(OcaError::SaidMismatch { expected, actual }) = parse_zip(&zip_from_files(&[
+            ("meta.json", &meta),
+            (&format!("{root}.json"), &capture_base),
+        ])) else {
+            panic!("expected a SAID mismatch");
+        };
+        assert_eq!(expected, root);
+        assert_ne!(actual, root);
+    }
+
+    #[test]
+    fn parse_zip_lenient_and_strict() {
+        let mut attributes = BTreeMap::<String, AttributeType>::new();
+        attributes.insert("givenName".to_string(), "Text".into());
+        let mut capture_base = CaptureBase::new(attributes, vec![]);
+        capture_base.update_digest().unwrap();
+        let root = capture_base.digest.clone();
+        let mut label = OcaLayer::new_label_layer(
+            &root,
+            "en",
+            BTreeMap::from([("givenName".to_string(), "Given name".to_string())]),
+            vec![],
+            BTreeMap::new(),
+        );
+        label.update_digest().unwrap();
+        let meta = serde_json::json!({
+            "root": root,
+            "files": { root.clone(): {
+                "label (en)": label.digest(),
+                "label (de)": 42,
+                "format": "EMissingMissingMissingMissingMissingMissing",
+            }}
+        });
+        let bundle = zip_from_files(&[
+            ("meta.json", &meta.to_string()),
+            (
+                &format!("{root}.json"),
+                &serde_json::to_string(&capture_base).unwrap(),
+            ),
+            (
+                &format!("{}.json", label.digest()),
+                &serde_json::to_string(&label).unwrap(),
+            ),
+            ("notes.txt", "left over"),
+        ]);
+
+        let (oca, warnings) = parse_zip_with_options(&bundle, &ParseOptions::default()).unwrap();
+        assert_eq!(oca.overlays.len(), 1);
+        assert_eq!(
+            warnings,
+            vec![
+                ParseWarning::SkippedOverlay {
+                    name: "label (de)".into(),
+                    reason: SkipReason::InvalidReference,
+                },
+                ParseWarning::SkippedOverlay {
+                    name: "format".into(),
+                    reason: SkipReason::MissingFile(
+                        "EMissingMissingMissingMissingMissingMissing.json".into()
+                    ),
+                },
+                ParseWarning::UnreferencedFile("notes.txt".into()),
+            ]
+        );
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_zip_with_options(&bundle, &strict),
+            Err(OcaError::Inconsistent(ParseWarning::SkippedOverlay {
+                reason: SkipReason::InvalidReference,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn parse_zip_verifies_integrity() {
+        let mut attributes = BTreeMap::<String, AttributeType>::new();
+        attributes.insert("givenName".to_string(), "Text".into());
+        let mut capture_base = CaptureBase::new(attributes, vec![]);
+        capture_base.update_digest().unwrap();
+        let root = capture_base.digest.clone();
+        let foreign = "EForeignForeignForeignForeignForeignForeign";
+        let labels = BTreeMap::from([("givenName".to_string(), "Given name".to_string())]);
+        let formats = BTreeMap::from([("surname".to_string(), "[A-Z]+".to_string())]);
+        let oca = Oca {
+            capture_base,
+            overlays: vec![
+                (
+                    "label (en)".into(),
+                    OcaLayer::new_label_layer(foreign, "en", labels, vec![], BTreeMap::new()),
+                ),
+                (
+                    "format".into(),
+                    OcaLayer::new_format_layer(&root, BTreeMap::new()),
+                ),
+                (
+                    "format 2".into(),
+                    OcaLayer::new_format_layer(&root, formats),
+                ),
+            ]
+            .into(),
+            dependencies: BTreeMap::new(),
+        };
+        let bundle = generate_zip(oca).unwrap();
+
+        let violations = vec![
+            IntegrityViolation::CaptureBaseMismatch {
+                overlay: "label (en)".into(),
+                expected: root.clone(),
+                actual: foreign.into(),
+            },
+            IntegrityViolation::DuplicateOverlay {
+                overlay: "format 2".into(),
+                overlay_type: "spec/overlays/format/1.0".into(),
+                language: None,
+            },
+            IntegrityViolation::UnknownAttribute {
+                overlay: "format 2".into(),
+                attribute: "surname".into(),
+            },
+        ];
+        assert_eq!(parse_zip(&bundle).unwrap().overlays.len(), 3);
+        let (oca, warnings) = parse_zip_with_options(&bundle, &ParseOptions::default()).unwrap();
+        assert_eq!(oca.integrity_violations(), violations);
+        assert_eq!(
+            warnings,
+            violations
+                .iter()
+                .cloned()
+                .map(ParseWarning::from)
+                .collect::<Vec<_>>()
+        );
+        assert!(matches!(warnings[1], ParseWarning::DuplicateOverlay { .. }));
+
+        let unchecked = ParseOptions {
+            verify_integrity: false,
+            ..Default::default()
+        };
+        let (_, warnings) = parse_zip_with_options(&bundle, &unchecked).unwrap();
+        assert_eq!(warnings.len(), 1);
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_zip_with_options(&bundle, &strict),
+            Err(OcaError::Inconsistent(
+                ParseWarning::CaptureBaseMismatch { .. }
+            ))
+        ));
+        let Err(OcaError::Integrity(found)) = oca.verify_integrity() else {
+            panic!("expected integrity violations");
+        };
+        assert_eq!(found, violations);
+    }
+
+    #[test]
+    fn round_trip_oca_1_0_overlays() {
+        let oca = builder::OcaBuilder::new()
+            .add_attribute("sex", "Text")
+            .add_attribute("height", "Numeric")
+            .add_attribute("categories", "Array[Text]")
+            .add_meta("en", "Driving licence", "A test licence")
+            .add_information("en", "height", "Body height")
+            .add_entry("en", "sex", "F", "female")
+            .add_entry("en", "sex", "M", "male")
+            .add_entry_codes("sex", &["F", "M"])
+            .metric_system("SI")
+            .add_unit("height", "cm")
+            .add_cardinality("categories", "1-")
+            .add_standard("sex", "ISO/IEC 5218")
+            .add_sensitive("sex")
+            .add_subset("sex")
+            .add_subset("height")
+            .build()
+            .unwrap();
+        let overlays = oca.overlays().clone();
+        let parsed = parse_zip(&generate_zip(oca).unwrap()).unwrap();
+
+        assert_eq!(parsed.overlays().len(), overlays.len());
+        for (name, overlay) in overlays.iter() {
+            let parsed_overlay = parsed.overlays().get(name).unwrap();
+            assert_eq!(parsed_overlay.digest(), overlay.digest());
+            assert_eq!(parsed_overlay.overlay_type(), overlay.overlay_type());
+            assert_eq!(
+                serde_json::to_string(parsed_overlay).unwrap(),
+                serde_json::to_string(overlay).unwrap()
+            );
+        }
+        let entry = parsed.overlays().first_of::<Entry>().unwrap();
+        assert_eq!(entry.entry_for("sex", "F"), Some("female"));
+        let unit = parsed.overlays().first_of::<Unit>().unwrap();
+        assert_eq!(unit.unit_for("height"), Some("cm"));
+        let meta = parsed.overlays().for_language::<Meta>("en-GB").unwrap();
+        assert_eq!(meta.name(), "Driving licence");
+        assert_eq!(
+            parsed
+                .overlays()
+                .first_of::<Sensitive>()
+                .unwrap()
+                .attributes(),
+            ["sex"]
+        );
+        assert_eq!(
+            parsed
+                .overlays()
+                .first_of::<Subset>()
+                .unwrap()
+                .attributes()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn third_party_bundle_round_trips_losslessly() {
+        let placeholder = "#".repeat(44);
+        let with_said = |template: String| {
+            let said = said::calculate_said(&template);
+            (said.clone(), template.replace(&placeholder, &said))
+        };
+        let (root, capture_base) = with_said(format!(
+            r#"{{"digest":"{placeholder}","type":"spec/capture_base/1.0","attributes":{{"name":"Text","age":"Numeric"}},"issuer_note":"kept","classification":null,"flagged_attributes":[]}}"#
+        ));
+        let (label_said, label) = with_said(format!(
+            r#"{{"type":"spec/overlays/label/1.0","language":"en","capture_base":"{root}","digest":"{placeholder}","attribute_labels":{{"name":"Name","age":"Age"}},"x-translator":{{"name":"ACME"}},"attribute_categories":[],"category_labels":{{}}}}"#
+        ));
+        let (style_said, style) = with_said(format!(
+            r#"{{"capture_base":"{root}","digest":"{placeholder}","type":"spec/overlays/style/1.0","style_json":{{"title":"T","subtitle":"S","cardColor":1,"textColor":"dark","backgroundCard":null,"orderedProperties":["name"],"logo":"data:image/png;base64,"}},"layout":"compact"}}"#
+        ));
+        let meta = serde_json::json!({
+            "root": root,
+            "files": { root.clone(): { "label (en)": label_said, "style": style_said } }
+        });
+        let bundle = zip_from_files(&[
+            ("meta.json", &meta.to_string()),
+            (&format!("{root}.json"), &capture_base),
+            (&format!("{label_said}.json"), &label),
+            (&format!("{style_said}.json"), &style),
+        ]);
+
+        let oca = parse_zip(&bundle).unwrap();
+        assert_eq!(
+            oca.style().unwrap().extra.get("logo"),
+            Some(&Value::String("data:image/png;base64,".into()))
+        );
+        let regenerated = generate_zip(oca).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(regenerated)).unwrap();
+        for (name, original) in [
+            (&root, &capture_base),
+            (&label_said, &label),
+            (&style_said, &style),
+        ] {
+            let mut file = archive.by_name(&format!("{name}.json")).unwrap();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut file, &mut content).unwrap();
+            assert_eq!(&content, original);
+        }
+
+        // a changed overlay is written from the model with a new digest
+        let mut oca = parse_zip(&bundle).unwrap();
+        let overlays = std::mem::take(&mut oca.overlays);
+        for (name, overlay) in overlays {
+            let mut value = serde_json::to_value(&overlay).unwrap();
+            if name == "label (en)" {
+                value["attribute_labels"]["name"] = "Full name".into();
+            }
+            oca.overlays
+                .push(name, serde_json::from_value(value).unwrap());
+        }
+        let json = parse_bundle(&generate_json_bundle(oca).unwrap()).unwrap();
+        let label = json.overlays().get("label (en)").unwrap();
+        assert_ne!(label.digest(), label_said);
+        assert_eq!(json.capture_base().digest(), root);
+    }
+
+    #[test]
+    fn generate_zip_is_deterministic() {
+        let oca = || {
+            builder::OcaBuilder::new()
+                .add_attribute("surname", "Text")
+                .add_attribute("givenName", "Text")
+                .add_label("en", "givenName", "Given name")
+                .add_label("de", "givenName", "Vorname")
+                .add_format("surname", "^.*$")
+                .build()
+                .unwrap()
+        };
+        let first = generate_zip(oca()).unwrap();
+        assert_eq!(first, generate_zip(oca()).unwrap());
+
+        let archive = zip::ZipArchive::new(std::io::Cursor::new(&first[..])).unwrap();
+        let names = archive.file_names().collect::<Vec<_>>();
+        let mut sorted = names[1..].to_vec();
+        sorted.sort();
+        assert_eq!(names[0], "meta.json");
+        assert_eq!(names[1..], sorted[..]);
+
+        let deflate = GenerateOptions { deflate: true };
+        let deflated = generate_zip_with_options(oca(), &deflate).unwrap();
+        assert_eq!(
+            deflated,
+            generate_zip_with_options(oca(), &deflate).unwrap()
+        );
+        assert_ne!(deflated, first);
+        assert_eq!(
+            parse_zip(&deflated).unwrap().overlays().len(),
+            parse_zip(&first).unwrap().overlays().len()
+        );
+    }
+    #[test]
+    fn json_bundle_round_trip() {
+        let oca = builder::OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_label("en", "givenName", "Given name")
+            .add_label("de", "givenName", "Vorname")
+            .build()
+            .unwrap();
+        let json = generate_json_bundle(oca.clone()).unwrap();
+        let zip = generate_zip(oca).unwrap();
+        let from_json = parse_bundle(&json).unwrap();
+        let from_zip = parse_bundle(&zip).unwrap();
+        assert_eq!(
+            serde_json::to_value(&from_json).unwrap(),
+            serde_json::to_value(&from_zip).unwrap()
+        );
+
+        let tampered = String::from_utf8(json).unwrap().replace("Vorname", "Name");
+        assert!(matches!(
+            parse_bundle_with_options(tampered.as_bytes(), &ParseOptions::default()),
+            Err(OcaError::SaidMismatch { .. })
+        ));
+        assert!(matches!(
+            parse_bundle(b"{\"overlays\": {}}"),
+            Err(OcaError::InvalidBundle(_))
+        ));
+    }
+    #[test]
+    fn legacy_saids_are_opt_in() {
+        let oca = builder::OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_label("en", "givenName", "Given name")
+            .build()
+            .unwrap();
+        let mut bundle: Value =
+            serde_json::from_slice(&generate_json_bundle(oca).unwrap()).unwrap();
+        // re-seal the overlays the way earlier versions of this crate did
+        for overlay in bundle["overlays"].as_object_mut().unwrap().values_mut() {
+            overlay["digest"] = Value::String("#".repeat(44));
+            let digest = blake3::hash(overlay.to_string().as_bytes());
+            overlay["digest"] = Value::String(format!(
+                "E{}",
+                base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(digest.as_bytes())
+            ));
+        }
+        let legacy = bundle.to_string();
+        assert!(matches!(
+            parse_bundle(legacy.as_bytes()),
+            Err(OcaError::SaidMismatch { .. })
+        ));
+        let lenient = ParseOptions {
+            accept_legacy_saids: true,
+            ..Default::default()
+        };
+        let (parsed, _) = parse_bundle_with_options(legacy.as_bytes(), &lenient).unwrap();
+        assert_eq!(
+            parsed.label("en").and_then(|l| l.label_for("givenName")),
+            Some("Given name")
+        );
+    }
+    #[test]
+    fn nested_bundles() {
+        let address = builder::OcaBuilder::new()
+            .add_attribute("street", "Text")
+            .add_attribute("city", "Text")
+            .add_label("en", "street", "Street")
+            .build()
+            .unwrap();
+        let reference = AttributeType::Reference(address.capture_base().digest().to_string());
+        let person = builder::OcaBuilder::new()
+            .add_attribute("name", "Text")
+            .add_attribute("address", reference.clone())
+            .add_attribute(
+                "previousAddresses",
+                AttributeType::Array(Box::new(reference.clone())),
+            )
+            .add_dependency(address.clone())
+            .build()
+            .unwrap();
+
+        for parsed in [
+            parse_zip(&generate_zip(person.clone()).unwrap()).unwrap(),
+            parse_bundle(&generate_json_bundle(person.clone()).unwrap()).unwrap(),
+        ] {
+            assert_eq!(parsed.dependencies().len(), 1);
+            let nested = parsed.referenced_bundle("address").unwrap();
+            assert_eq!(
+                nested.capture_base().digest(),
+                address.capture_base().digest()
+            );
+            assert_eq!(
+                nested.label("en").and_then(|l| l.label_for("street")),
+                Some("Street")
+            );
+            assert!(parsed.referenced_bundle("previousAddresses").is_some());
+            assert!(parsed.referenced_bundle("name").is_none());
+            let graph = parsed.dependency_graph();
+            assert_eq!(
+                graph[parsed.capture_base().digest()],
+                vec![address.capture_base().digest(); 2]
+            );
+        }
+
+        let result = builder::OcaBuilder::new()
+            .add_attribute("address", reference)
+            .build();
+        assert!(matches!(
+            result,
+            Err(OcaError::Integrity(violations))
+                if matches!(violations[..], [IntegrityViolation::UnresolvedReference { .. }])
+        ));
+    }
+    #[test]
+    fn style_json_attribute_types() {
+        let style_json: StyleJsonFile = serde_json::from_value(serde_json::json!({
+            "attributes": {
+                "name": { "displayName": "Name", "fieldType": "STRING" },
+                "birth": { "displayName": "Birth", "fieldType": "DATEOFBIRTH" },
+                "height": { "displayName": "Height", "fieldType": "NUMBER" },
+                "adult": { "displayName": "Adult", "fieldType": "BOOLEAN" },
+                "portrait": { "displayName": "Portrait", "fieldType": "IMAGE" }
+            },
+            "style": {
+                "title": "", "subtitle": "", "cardColor": 0, "textColor": "dark",
+                "backgroundCard": null, "orderedProperties": []
+            }
+        }))
+        .unwrap();
+        let oca = oca_from_style_json(style_json).unwrap();
+        let attribute_type = |name: &str| oca.capture_base().attribute_type(name).cloned();
+        assert_eq!(attribute_type("name"), Some(AttributeType::Text));
+        assert_eq!(attribute_type("birth"), Some(AttributeType::DateTime));
+        assert_eq!(attribute_type("height"), Some(AttributeType::Numeric));
+        assert_eq!(attribute_type("adult"), Some(AttributeType::Boolean));
+        assert_eq!(attribute_type("portrait"), Some(AttributeType::Binary));
+        assert_eq!(
+            oca.character_encoding_for("portrait"),
+            Some(Encoding::Base64)
+        );
+    }
+    #[test]
+    fn multilingual_style_json() {
+        let style_json: StyleJsonFile = serde_json::from_value(serde_json::json!({
+            "attributes": {
+                "name": {
+                    "displayName": "Name",
+                    "displayNames": { "de": "Name", "fr": "Nom" },
+                    "fieldType": "STRING"
+                },
+                "birth": {
+                    "displayName": "Date of birth",
+                    "displayNames": { "de": "Geburtsdatum" },
+                    "fieldType": "DATEOFBIRTH"
+                }
+            },
+            "style": {
+                "title": "Identity card", "subtitle": "{{ name }}", "cardColor": 0,
+                "textColor": "dark", "backgroundCard": null, "orderedProperties": ["name"]
+            },
+            "translations": {
+                "de": { "title": "Identitätskarte" },
+                "fr": { "title": "Carte d'identité", "subtitle": "{{ name }} (FR)" }
+            }
+        }))
+        .unwrap();
+        let oca = oca_from_style_json(style_json).unwrap();
+        let label = |language: &str, attribute: &str| {
+            oca.label(language)
+                .and_then(|label| label.label_for(attribute))
+                .map(str::to_string)
+        };
+        assert_eq!(label("de", "birth").as_deref(), Some("Geburtsdatum"));
+        assert_eq!(label("fr", "name").as_deref(), Some("Nom"));
+        assert_eq!(label("fr", "birth").as_deref(), Some("Date of birth"));
+        assert_eq!(label("en", "name").as_deref(), Some("Name"));
+        assert_eq!(label("en", "birth").as_deref(), Some("Date of birth"));
+        let metas = oca
+            .overlays()
+            .overlays_of::<Meta>()
+            .map(|meta| (meta.language(), meta.name(), meta.description()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            metas,
+            [
+                ("de", "Identitätskarte", "{{ name }}"),
+                ("fr", "Carte d'identité", "{{ name }} (FR)")
+            ]
+        );
+
+        let style_json = style_json_from_oca(&oca, "fr");
+        assert_eq!(style_json.attributes["name"].display_name, "Nom");
+        assert_eq!(style_json.attributes["name"].display_names.len(), 3);
+        let again = oca_from_style_json(style_json).unwrap();
+        assert_eq!(
+            serde_json::to_value(&again).unwrap(),
+            serde_json::to_value(&oca).unwrap()
+        );
+    }
+    #[test]
+    fn style_json_round_trip() {
+        use models::Attribute;
+
+        let field_types = [
+            AttributeFieldType::String,
+            AttributeFieldType::Date,
+            AttributeFieldType::DateTime,
+            AttributeFieldType::Time,
+            AttributeFieldType::DateOfBirth,
+            AttributeFieldType::Image,
+            AttributeFieldType::Boolean,
+            AttributeFieldType::Number,
+        ];
+        // a simple LCG, so every run checks the same generated style JSONs
+        let mut state = 7u64;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % bound
+        };
+        for _ in 0..64 {
+            let attributes = (0..1 + next(6))
+                .map(|i| {
+                    let attribute = Attribute {
+                        display_name: format!("Attribute {i}"),
+                        field_type: field_types[next(field_types.len())],
+                        display_names: BTreeMap::new(),
+                    };
+                    (format!("attribute{i}"), attribute)
+                })
+                .collect::<BTreeMap<_, _>>();
+            let mut ordered_properties = attributes.keys().cloned().collect::<Vec<_>>();
+            let rotation = next(ordered_properties.len());
+            ordered_properties.rotate_left(rotation);
+            let style = StyleJson {
+                title: "{{ attribute0 }}".into(),
+                subtitle: "Generated".into(),
+                card_color: next(1 << 24) as u64,
+                text_color: "dark".into(),
+                background_card: None,
+                ordered_properties,
+                ..Default::default()
+            };
+            let original = StyleJsonFile {
+                attributes: attributes.clone(),
+                style,
+                translations: BTreeMap::new(),
+            };
+            let oca = oca_from_style_json(original.clone()).unwrap();
+            // the capture base, and so its SAID, only depends on the attribute types
+            assert!(oca.capture_base().extra().is_empty());
+            // the field types survive a bundle round trip as well
+            let parsed = parse_zip(&generate_zip(oca.clone()).unwrap()).unwrap();
+            let style_json = style_json_from_oca(&parsed, "en");
+
+            assert_eq!(
+                serde_json::to_value(&style_json.style).unwrap(),
+                serde_json::to_value(&original.style).unwrap()
+            );
+            for (name, attribute) in &attributes {
+                let converted = &style_json.attributes[name];
+                assert_eq!(converted.display_name, attribute.display_name);
+                assert_eq!(converted.field_type, attribute.field_type);
+            }
+            let again = oca_from_style_json(style_json).unwrap();
+            assert_eq!(
+                serde_json::to_value(&again).unwrap(),
+                serde_json::to_value(&oca).unwrap()
+            );
+        }
+    }
     #[test]
     fn calculate_said() {
         let mut layer = OcaLayer::Conformance(Conformance::new(
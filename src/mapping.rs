@@ -0,0 +1,872 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Evaluation of the expressions of attribute mapping overlays.
+//!
+//! An expression is one of
+//!
+//! * a path into the credential, `vc.givenName` or any other JSONPath with or
+//!   without the leading `$.`. Paths with wildcards, `..` or filters, e.g.
+//!   `vc.items[*].name`, project all matches into a list,
+//! * a constant, `'CH'`, `42`, `true` or `null`,
+//! * a transform, `concat(vc.givenName, ' ', vc.surname)`,
+//!   `split(vc.name, ' ', 0)` or `date(vc.birthDate, '%Y-%m-%d', '%Y%m%d')`,
+//! * alternatives separated by `|`, of which the first with a value is used,
+//!   e.g. `vc.familyName | vc.surname`.
+//!
+//! The attribute `$` is special: the object it maps to is spread into the
+//! attributes.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde_json::{Map, Value};
+
+use crate::models::AttributeMapping;
+
+/// The attribute whose mapped object is spread into the attributes.
+pub const SPREAD: &str = "$";
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum MappingError {
+    #[error("invalid mapping {expression} at {position}: {message}")]
+    Syntax {
+        expression: String,
+        position: usize,
+        message: String,
+    },
+    #[error("invalid path {path}: {reason}")]
+    InvalidPath { path: String, reason: String },
+    #[error("{function} failed: {reason}")]
+    Transform {
+        function: &'static str,
+        reason: String,
+    },
+    #[error("cannot write {attribute} back: {reason}")]
+    Unwritable { attribute: String, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Concat,
+    Split,
+    Date,
+}
+
+impl Function {
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Concat => "concat",
+            Function::Split => "split",
+            Function::Date => "date",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "concat" => Some(Function::Concat),
+            "split" => Some(Function::Split),
+            "date" => Some(Function::Date),
+            _ => None,
+        }
+    }
+    fn accepts(self, count: usize) -> bool {
+        match self {
+            Function::Concat => count >= 1,
+            Function::Split => (2..=3).contains(&count),
+            Function::Date => count == 3,
+        }
+    }
+    fn error(self, reason: String) -> MappingError {
+        MappingError::Transform {
+            function: self.name(),
+            reason,
+        }
+    }
+}
+
+/// A parsed mapping expression, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// A JSONPath without the leading `$.`.
+    Path(String),
+    Constant(Value),
+    Call {
+        function: Function,
+        arguments: Vec<Expression>,
+    },
+    Fallback(Vec<Expression>),
+}
+
+impl Expression {
+    pub fn parse(expression: &str) -> Result<Self, MappingError> {
+        let mut parser = Parser {
+            source: expression,
+            position: 0,
+        };
+        let parsed = parser.expression()?;
+        parser.skip_whitespace();
+        if parser.position < expression.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(parsed)
+    }
+
+    /// The value of the expression in `data`, `None` if a path does not match.
+    pub fn evaluate(&self, data: &Value) -> Result<Option<Value>, MappingError> {
+        match self {
+            Expression::Path(path) => select(data, path),
+            Expression::Constant(value) => Ok(Some(value.clone())),
+            Expression::Fallback(alternatives) => {
+                for alternative in alternatives {
+                    match alternative.evaluate(data)? {
+                        Some(Value::Null) | None => continue,
+                        value => return Ok(value),
+                    }
+                }
+                Ok(None)
+            }
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(data))
+                    .collect::<Result<Vec<_>, _>>()?;
+                apply(*function, arguments)
+            }
+        }
+    }
+
+    /// Writes `value` to where the expression reads it from. Paths are written
+    /// as they are, continuing at the longest existing key for dotted names, so
+    /// keys containing dots must be bracket-quoted to be created, e.g.
+    /// `['org.iso.18013.5.1'].family_name`. The first alternative of a fallback
+    /// is used and dates are converted back. Constants and the results of `concat` and `split` are
+    /// derived values and not written.
+    pub fn write(&self, target: &mut Value, value: Value) -> Result<(), MappingError> {
+        match self {
+            Expression::Path(path) => {
+                let segments = write_segments(path).ok_or_else(|| MappingError::InvalidPath {
+                    path: path.clone(),
+                    reason: "only keys, indexes and [*] can be written".to_string(),
+                })?;
+                write_path(target, &segments, value).map_err(|reason| MappingError::InvalidPath {
+                    path: path.clone(),
+                    reason,
+                })
+            }
+            Expression::Fallback(alternatives) => match alternatives.first() {
+                Some(first) => first.write(target, value),
+                None => Ok(()),
+            },
+            Expression::Call {
+                function: Function::Date,
+                arguments,
+            } => {
+                let formats = (
+                    arguments[1].evaluate(&Value::Null)?,
+                    arguments[2].evaluate(&Value::Null)?,
+                );
+                let (Some(Value::String(from)), Some(Value::String(to))) = formats else {
+                    return Err(Function::Date.error("formats must be text".to_string()));
+                };
+                let converted = each(Function::Date, value, &|date| {
+                    reformat_date(date, &to, &from)
+                })?;
+                match converted {
+                    Some(converted) => arguments[0].write(target, converted),
+                    None => Ok(()),
+                }
+            }
+            Expression::Constant(_) | Expression::Call { .. } => Ok(()),
+        }
+    }
+}
+
+impl AttributeMapping {
+    /// Maps credential `data` to an object of capture base attributes, leaving
+    /// out attributes whose mapping is invalid or fails.
+    pub fn map_json(&self, data: &Value) -> Value {
+        let mut attributes = Map::new();
+        for (attribute, expression) in self.attribute_mapping() {
+            let Ok(Some(value)) = Expression::parse(expression).and_then(|e| e.evaluate(data))
+            else {
+                continue;
+            };
+            insert(&mut attributes, attribute, value);
+        }
+        Value::Object(attributes)
+    }
+
+    /// Like [`AttributeMapping::map_json`], but fails on the first invalid or
+    /// failing mapping. Paths without match are still left out.
+    pub fn try_map_json(&self, data: &Value) -> Result<Value, MappingError> {
+        let mut attributes = Map::new();
+        for (attribute, expression) in self.attribute_mapping() {
+            if let Some(value) = Expression::parse(expression)?.evaluate(data)? {
+                insert(&mut attributes, attribute, value);
+            }
+        }
+        Ok(Value::Object(attributes))
+    }
+
+    /// Writes capture base `attributes` back into a credential shaped object,
+    /// the reverse of [`AttributeMapping::map_json`], e.g. for issuance.
+    /// Attributes without a mapping of their own go to the object the `$`
+    /// attribute is mapped to, if there is one.
+    pub fn reverse_map_json(&self, attributes: &Value) -> Result<Value, MappingError> {
+        let Value::Object(attributes) = attributes else {
+            return Err(MappingError::Unwritable {
+                attribute: SPREAD.to_string(),
+                reason: "attributes must be an object".to_string(),
+            });
+        };
+        let mapping = self.attribute_mapping();
+        let mut credential = Value::Object(Map::new());
+        if let Some(spread) = mapping.get(SPREAD) {
+            let unmapped = attributes
+                .iter()
+                .filter(|(attribute, _)| !mapping.contains_key(*attribute))
+                .map(|(attribute, value)| (attribute.clone(), value.clone()))
+                .collect::<Map<_, _>>();
+            Expression::parse(spread)?.write(&mut credential, Value::Object(unmapped))?;
+        }
+        for (attribute, expression) in mapping {
+            let Some(value) = attributes.get(attribute).filter(|_| attribute != SPREAD) else {
+                continue;
+            };
+            Expression::parse(expression)?
+                .write(&mut credential, value.clone())
+                .map_err(|e| MappingError::Unwritable {
+                    attribute: attribute.clone(),
+                    reason: e.to_string(),
+                })?;
+        }
+        Ok(credential)
+    }
+}
+
+fn insert(attributes: &mut Map<String, Value>, attribute: &str, value: Value) {
+    if attribute != SPREAD {
+        attributes.insert(attribute.to_string(), value);
+    } else if let Value::Object(inner) = value {
+        attributes.extend(inner);
+    }
+}
+
+fn select(data: &Value, path: &str) -> Result<Option<Value>, MappingError> {
+    if is_plain(path) {
+        if let Some(value) = select_dotted(data, path) {
+            return Ok(Some(value.clone()));
+        }
+    }
+    let query = if path.is_empty() || path.starts_with('[') {
+        format!("${path}")
+    } else {
+        format!("$.{path}")
+    };
+    let matches = jsonpath_lib::select(data, &query).map_err(|e| MappingError::InvalidPath {
+        path: path.to_string(),
+        reason: format!("{e:?}"),
+    })?;
+    if is_projection(path) {
+        if matches.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(Value::Array(matches.into_iter().cloned().collect())));
+    }
+    Ok(matches.first().map(|value| (*value).clone()))
+}
+
+fn is_plain(path: &str) -> bool {
+    !path.is_empty()
+        && path
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+// Resolves a dotted path against keys that may contain dots themselves, such
+// as the mdoc namespace in `org.iso.18013.5.1.family_name`. Longer keys are
+// tried first.
+fn select_dotted<'a>(data: &'a Value, path: &str) -> Option<&'a Value> {
+    let Value::Object(object) = data else {
+        return None;
+    };
+    let mut keys: Vec<_> = object
+        .keys()
+        .filter(|key| {
+            path.strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .collect();
+    keys.sort_by_key(|key| std::cmp::Reverse(key.len()));
+    keys.into_iter().find_map(|key| match &path[key.len()..] {
+        "" => object.get(key),
+        rest => select_dotted(&object[key], &rest[1..]),
+    })
+}
+
+fn is_projection(path: &str) -> bool {
+    path.contains('*') || path.contains("..") || path.contains("?(")
+}
+
+fn apply(function: Function, arguments: Vec<Option<Value>>) -> Result<Option<Value>, MappingError> {
+    let text = |argument: &Option<Value>, what: &str| match argument {
+        Some(Value::String(text)) => Ok(text.clone()),
+        _ => Err(function.error(format!("{what} must be text"))),
+    };
+    match function {
+        Function::Concat => {
+            if arguments.iter().all(Option::is_none) {
+                return Ok(None);
+            }
+            let mut result = String::new();
+            for argument in &arguments {
+                match argument {
+                    None | Some(Value::Null) => {}
+                    Some(Value::String(text)) => result.push_str(text),
+                    Some(Value::Number(number)) => result.push_str(&number.to_string()),
+                    Some(Value::Bool(boolean)) => result.push_str(&boolean.to_string()),
+                    Some(_) => {
+                        return Err(function.error("cannot concatenate lists or objects".into()))
+                    }
+                }
+            }
+            Ok(Some(Value::String(result)))
+        }
+        Function::Split => {
+            let separator = text(&arguments[1], "separator")?;
+            let index = match arguments.get(2) {
+                None => None,
+                Some(Some(Value::Number(index))) => Some(
+                    index
+                        .as_u64()
+                        .ok_or_else(|| function.error("index must be a natural number".into()))?
+                        as usize,
+                ),
+                Some(_) => return Err(function.error("index must be a number".into())),
+            };
+            let Some(value) = arguments.into_iter().next().flatten() else {
+                return Ok(None);
+            };
+            each(function, value, &|text| {
+                let mut parts = text.split(separator.as_str());
+                Ok(match index {
+                    Some(index) => parts.nth(index).map(|part| Value::String(part.to_string())),
+                    None => Some(Value::Array(
+                        parts.map(|part| Value::String(part.to_string())).collect(),
+                    )),
+                })
+            })
+        }
+        Function::Date => {
+            let from = text(&arguments[1], "source format")?;
+            let to = text(&arguments[2], "target format")?;
+            let Some(value) = arguments.into_iter().next().flatten() else {
+                return Ok(None);
+            };
+            each(function, value, &|date| reformat_date(date, &from, &to))
+        }
+    }
+}
+
+/// Applies `f` to a text, or to each text of a list.
+fn each(
+    function: Function,
+    value: Value,
+    f: &dyn Fn(&str) -> Result<Option<Value>, MappingError>,
+) -> Result<Option<Value>, MappingError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(text) => f(&text),
+        Value::Number(number) => f(&number.to_string()),
+        Value::Array(items) => {
+            let mut results = vec![];
+            for item in items {
+                results.push(each(function, item, f)?.unwrap_or(Value::Null));
+            }
+            Ok(Some(Value::Array(results)))
+        }
+        _ => Err(function.error("expected text".into())),
+    }
+}
+
+fn reformat_date(date: &str, from: &str, to: &str) -> Result<Option<Value>, MappingError> {
+    let formatted = if let Ok(date_time) = NaiveDateTime::parse_from_str(date, from) {
+        date_time.format(to).to_string()
+    } else if let Ok(date) = NaiveDate::parse_from_str(date, from) {
+        date.format(to).to_string()
+    } else if let Ok(time) = NaiveTime::parse_from_str(date, from) {
+        time.format(to).to_string()
+    } else {
+        return Err(Function::Date.error(format!("{date} does not match {from}")));
+    };
+    Ok(Some(Value::String(formatted)))
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    /// A dotted name, which may be part of a key containing dots.
+    Name(String),
+    /// A bracket-quoted key, used as it is.
+    Key(String),
+    Index(usize),
+    All,
+}
+
+/// Splits a path into names, keys, indexes and `[*]`, or `None` if it uses
+/// anything else.
+fn write_segments(path: &str) -> Option<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    while !rest.is_empty() {
+        if let Some(bracket) = rest.strip_prefix('[') {
+            let (inner, after) = bracket.split_once(']')?;
+            let segment = match inner {
+                "*" => Segment::All,
+                quoted
+                    if quoted.len() >= 2
+                        && (quoted.starts_with('\'') || quoted.starts_with('"')) =>
+                {
+                    Segment::Key(quoted[1..quoted.len() - 1].to_string())
+                }
+                index => Segment::Index(index.parse().ok()?),
+            };
+            segments.push(segment);
+            rest = after;
+        } else {
+            let key = rest.strip_prefix('.').unwrap_or(rest);
+            if key.starts_with('.') {
+                return None;
+            }
+            let end = key.find(['.', '[']).unwrap_or(key.len());
+            match &key[..end] {
+                "" => {}
+                "*" => segments.push(Segment::All),
+                name => segments.push(Segment::Name(name.to_string())),
+            }
+            rest = &key[end..];
+        }
+    }
+    Some(segments)
+}
+
+fn write_path(target: &mut Value, segments: &[Segment], value: Value) -> Result<(), String> {
+    let Some((segment, rest)) = segments.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+    match segment {
+        Segment::Name(key) | Segment::Key(key) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let Value::Object(object) = target else {
+                return Err(format!("{key} is not in an object"));
+            };
+            let (key, rest) = object_key(object, key, segments);
+            write_path(object.entry(key).or_insert(Value::Null), rest, value)
+        }
+        Segment::Index(index) => {
+            let items = array(target)?;
+            if items.len() <= *index {
+                items.resize(index + 1, Value::Null);
+            }
+            write_path(&mut items[*index], rest, value)
+        }
+        Segment::All => {
+            let values = match value {
+                Value::Array(values) => values,
+                value => vec![value],
+            };
+            let items = array(target)?;
+            if items.len() < values.len() {
+                items.resize(values.len(), Value::Null);
+            }
+            for (item, value) in items.iter_mut().zip(values) {
+                write_path(item, rest, value)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The key of `object` the path `segments`, starting with `key`, continues at
+/// and the remaining segments. Like [`select_dotted`], names are joined to the
+/// longest existing key containing dots, e.g. an mdoc namespace.
+fn object_key<'a>(
+    object: &Map<String, Value>,
+    key: &str,
+    segments: &'a [Segment],
+) -> (String, &'a [Segment]) {
+    let names = segments
+        .iter()
+        .map_while(|segment| match segment {
+            Segment::Name(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for length in (2..=names.len()).rev() {
+        let key = names[..length].join(".");
+        if object.contains_key(&key) {
+            return (key, &segments[length..]);
+        }
+    }
+    (key.to_string(), &segments[1..])
+}
+
+fn array(target: &mut Value) -> Result<&mut Vec<Value>, String> {
+    if target.is_null() {
+        *target = Value::Array(vec![]);
+    }
+    match target {
+        Value::Array(items) => Ok(items),
+        _ => Err("index into something else than a list".to_string()),
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> MappingError {
+        MappingError::Syntax {
+            expression: self.source.to_string(),
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
+    }
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn expression(&mut self) -> Result<Expression, MappingError> {
+        let mut alternatives = vec![self.alternative()?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('|') {
+                break;
+            }
+            self.position += 1;
+            alternatives.push(self.alternative()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Expression::Fallback(alternatives)
+        })
+    }
+
+    fn alternative(&mut self) -> Result<Expression, MappingError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some(',' | ')' | '|') => {
+                Err(self.error("expected a path, constant or function"))
+            }
+            Some('\'') => self.string(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => {
+                let rest = self.rest();
+                let name_length = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                let name = &rest[..name_length];
+                let after = rest[name_length..].trim_start();
+                if name_length > 0 && after.starts_with('(') {
+                    return self.call(name_length);
+                }
+                let ends = after.is_empty() || after.starts_with([',', ')', '|']);
+                let constant = match name {
+                    "true" => Some(Value::Bool(true)),
+                    "false" => Some(Value::Bool(false)),
+                    "null" => Some(Value::Null),
+                    _ => None,
+                };
+                match constant {
+                    Some(constant) if ends => {
+                        self.position += name_length;
+                        Ok(Expression::Constant(constant))
+                    }
+                    _ => self.path(),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<Expression, MappingError> {
+        let start = self.position;
+        self.position += 1;
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '\'' => {
+                    self.position += offset + 1;
+                    return Ok(Expression::Constant(Value::String(text)));
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => text.push(escaped),
+                    None => break,
+                },
+                c => text.push(c),
+            }
+        }
+        self.position = start;
+        Err(self.error("unterminated text"))
+    }
+
+    fn number(&mut self) -> Result<Expression, MappingError> {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(rest.len());
+        let number = serde_json::from_str::<serde_json::Number>(&rest[..length])
+            .map_err(|_| self.error("invalid number"))?;
+        self.position += length;
+        Ok(Expression::Constant(Value::Number(number)))
+    }
+
+    fn call(&mut self, name_length: usize) -> Result<Expression, MappingError> {
+        let name = &self.rest()[..name_length];
+        let function = Function::from_name(name)
+            .ok_or_else(|| self.error(&format!("unknown function {name}")))?;
+        self.position += name_length;
+        self.skip_whitespace();
+        // the opening parenthesis
+        self.position += 1;
+        let mut arguments = vec![];
+        loop {
+            arguments.push(self.expression()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(')') => {
+                    self.position += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected , or )")),
+            }
+        }
+        if !function.accepts(arguments.len()) {
+            return Err(self.error(&format!(
+                "wrong number of arguments for {}",
+                function.name()
+            )));
+        }
+        Ok(Expression::Call {
+            function,
+            arguments,
+        })
+    }
+
+    fn path(&mut self) -> Result<Expression, MappingError> {
+        let start = self.position;
+        let mut depth = 0usize;
+        let mut quote = None;
+        for (offset, c) in self.rest().char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') if depth > 0 => quote = Some(c),
+                (None, '[' | '(') => depth += 1,
+                (None, ']' | ')') if depth > 0 => depth -= 1,
+                (None, ',' | ')' | '|') if depth == 0 => {
+                    self.position += offset;
+                    break;
+                }
+                (None, c) if c.is_whitespace() && depth == 0 => {
+                    self.position += offset;
+                    break;
+                }
+                _ => {}
+            }
+            if offset + c.len_utf8() == self.rest().len() {
+                self.position = self.source.len();
+                break;
+            }
+        }
+        if depth > 0 || quote.is_some() {
+            self.position = start;
+            return Err(self.error("unbalanced brackets"));
+        }
+        let path = &self.source[start..self.position];
+        let path = path
+            .strip_prefix("$.")
+            .or_else(|| path.strip_prefix('$'))
+            .unwrap_or(path);
+        Ok(Expression::Path(path.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::{Expression, MappingError};
+    use crate::models::OcaLayer;
+
+    fn mapping_layer(entries: &[(&str, &str)]) -> crate::models::AttributeMapping {
+        let entries = entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        let OcaLayer::AttributeMapping(mapping) =
+            OcaLayer::new_attribute_mapping_layer("E", entries)
+        else {
+            unreachable!()
+        };
+        mapping
+    }
+
+    #[test]
+    fn expressions() {
+        let data = json!({
+            "vc": {
+                "givenName": "Erika",
+                "surname": "Mustermann",
+                "birthDate": "1964-08-12",
+                "items": [{ "name": "A" }, { "name": "B" }],
+                "address": "Main Street 1, 3000 Bern"
+            }
+        });
+        let evaluate = |expression: &str| {
+            Expression::parse(expression)
+                .unwrap()
+                .evaluate(&data)
+                .unwrap()
+        };
+        assert_eq!(evaluate("vc.givenName"), Some(json!("Erika")));
+        assert_eq!(evaluate("$.vc.givenName"), Some(json!("Erika")));
+        assert_eq!(
+            evaluate("vc.familyName | vc.surname"),
+            Some(json!("Mustermann"))
+        );
+        assert_eq!(
+            evaluate("vc.familyName | 'unknown'"),
+            Some(json!("unknown"))
+        );
+        assert_eq!(evaluate("vc.familyName"), None);
+        assert_eq!(evaluate("vc.items[*].name"), Some(json!(["A", "B"])));
+        assert_eq!(evaluate("vc.items[1].name"), Some(json!("B")));
+        assert_eq!(
+            evaluate("concat(vc.givenName, ' ', vc.surname)"),
+            Some(json!("Erika Mustermann"))
+        );
+        assert_eq!(
+            evaluate("split(vc.address, ', ', 1)"),
+            Some(json!("3000 Bern"))
+        );
+        assert_eq!(
+            evaluate("split(vc.address, ', ')"),
+            Some(json!(["Main Street 1", "3000 Bern"]))
+        );
+        assert_eq!(
+            evaluate("date(vc.birthDate, '%Y-%m-%d', '%Y%m%d')"),
+            Some(json!("19640812"))
+        );
+        assert_eq!(evaluate("42"), Some(json!(42)));
+        assert_eq!(evaluate(r"'it\'s'"), Some(json!("it's")));
+
+        assert!(matches!(
+            Expression::parse("concat(vc.a"),
+            Err(MappingError::Syntax { .. })
+        ));
+        assert!(matches!(
+            Expression::parse("upper(vc.a)"),
+            Err(MappingError::Syntax { .. })
+        ));
+        assert!(matches!(
+            Expression::parse("date(vc.givenName, '%Y', '%Y')")
+                .unwrap()
+                .evaluate(&data),
+            Err(MappingError::Transform {
+                function: "date",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn map_and_reverse() {
+        let mapping = mapping_layer(&[
+            ("$", "vc"),
+            ("givenName", "vc.firstName | vc.givenName"),
+            ("birthDate", "date(vc.dob, '%Y-%m-%d', '%Y%m%d')"),
+            ("items", "vc.items[*].name"),
+            ("country", "'CH'"),
+        ]);
+        let credential = json!({
+            "vc": {
+                "givenName": "Erika",
+                "surname": "Mustermann",
+                "dob": "1964-08-12",
+                "items": [{ "name": "A" }, { "name": "B" }]
+            }
+        });
+        let attributes = mapping.try_map_json(&credential).unwrap();
+        assert_eq!(attributes["givenName"], "Erika");
+        assert_eq!(attributes["surname"], "Mustermann");
+        assert_eq!(attributes["birthDate"], "19640812");
+        assert_eq!(attributes["items"], json!(["A", "B"]));
+        assert_eq!(attributes["country"], "CH");
+        assert_eq!(mapping.map_json(&credential), attributes);
+
+        let reversed = mapping.reverse_map_json(&attributes).unwrap();
+        assert_eq!(
+            reversed,
+            json!({
+                "vc": {
+                    "surname": "Mustermann",
+                    "dob": "1964-08-12",
+                    "firstName": "Erika",
+                    "items": [{ "name": "A" }, { "name": "B" }]
+                }
+            })
+        );
+
+        // keys containing dots are bracket-quoted to be written back as one
+        let mdoc = json!({
+            "org.iso.18013.5.1": { "family_name": "Mustermann", "given_name": "Erika" }
+        });
+        let mapping = mapping_layer(&[
+            ("surname", "['org.iso.18013.5.1'].family_name"),
+            ("givenName", r#"["org.iso.18013.5.1"].given_name"#),
+        ]);
+        let attributes = mapping.try_map_json(&mdoc).unwrap();
+        assert_eq!(
+            attributes,
+            json!({ "surname": "Mustermann", "givenName": "Erika" })
+        );
+        assert_eq!(mapping.reverse_map_json(&attributes).unwrap(), mdoc);
+        // plain paths continue at the longest existing key
+        let mut target = json!({ "org.iso.18013.5.1": {} });
+        Expression::parse("org.iso.18013.5.1.age_over_18")
+            .unwrap()
+            .write(&mut target, json!(true))
+            .unwrap();
+        assert_eq!(
+            target,
+            json!({ "org.iso.18013.5.1": { "age_over_18": true } })
+        );
+
+        let broken = mapping_layer(&[("givenName", "vc.givenName"), ("surname", "concat(")]);
+        assert_eq!(
+            broken.map_json(&credential),
+            json!({ "givenName": "Erika" })
+        );
+        assert!(broken.try_map_json(&credential).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Decoding of ISO 18013-5 mdocs, e.g. mobile driving licences.
+//!
+//! The issuer signed items are collected into a JSON object keyed by
+//! namespace and element identifier, so an attribute mapping overlay can refer
+//! to `org.iso.18013.5.1.family_name`, or `['org.iso.18013.5.1'].family_name`
+//! if the attributes are written back. Dates are decoded to their text form
+//! and byte strings such as the `portrait` to base64. The issuer signature and
+//! the value digests are not checked.
+
+use base64::Engine;
+use ciborium::Value as Cbor;
+use serde_json::{Map, Number, Value};
+
+use crate::credential::{Credential, CredentialError};
+
+/// The namespace of the mobile driving licence elements.
+pub const MDL_NAMESPACE: &str = "org.iso.18013.5.1";
+
+// CBOR tags of RFC 8949 and RFC 8943
+const TAG_TDATE: u64 = 0;
+const TAG_EPOCH: u64 = 1;
+const TAG_ENCODED_CBOR: u64 = 24;
+const TAG_FULL_DATE: u64 = 1004;
+
+impl Credential {
+    /// Decodes an mdoc `IssuerSigned` structure, a `Document` containing one
+    /// or the first document of a `DeviceResponse`.
+    pub fn decode_mdoc(bytes: &[u8]) -> Result<Self, CredentialError> {
+        Ok(Credential::from_claims(decode_mdoc(bytes)?))
+    }
+}
+
+/// The issuer signed elements of an mdoc, keyed by namespace and element
+/// identifier.
+pub fn decode_mdoc(bytes: &[u8]) -> Result<Value, CredentialError> {
+    let value: Cbor =
+        ciborium::from_reader(bytes).map_err(|e| CredentialError::Cbor(e.to_string()))?;
+    let issuer_signed = issuer_signed(&value)?;
+    let Some(Cbor::Map(namespaces)) = get(issuer_signed, "nameSpaces") else {
+        return Err(malformed("missing nameSpaces"));
+    };
+    let mut claims = Map::new();
+    for (namespace, items) in namespaces {
+        let (Cbor::Text(namespace), Cbor::Array(items)) = (namespace, items) else {
+            return Err(malformed("namespaces must map names to item arrays"));
+        };
+        let mut elements = Map::new();
+        for item in items {
+            let item = encoded(item)?;
+            let Some(Cbor::Text(identifier)) = get(&item, "elementIdentifier") else {
+                return Err(malformed("item without elementIdentifier"));
+            };
+            let value =
+                get(&item, "elementValue").ok_or_else(|| malformed("item without elementValue"))?;
+            elements.insert(identifier.clone(), json(value)?);
+        }
+        claims.insert(namespace.clone(), Value::Object(elements));
+    }
+    Ok(Value::Object(claims))
+}
+
+fn issuer_signed(value: &Cbor) -> Result<&Cbor, CredentialError> {
+    if let Some(Cbor::Array(documents)) = get(value, "documents") {
+        let document = documents.first().ok_or_else(|| malformed("no documents"))?;
+        return issuer_signed(document);
+    }
+    if let Some(issuer_signed) = get(value, "issuerSigned") {
+        return Ok(issuer_signed);
+    }
+    Ok(value)
+}
+
+// Issuer signed items are wrapped as tagged byte strings so their digests can
+// be computed, plain items are accepted as well.
+fn encoded(item: &Cbor) -> Result<Cbor, CredentialError> {
+    match item {
+        Cbor::Tag(TAG_ENCODED_CBOR, inner) => match inner.as_ref() {
+            Cbor::Bytes(bytes) => ciborium::from_reader(bytes.as_slice())
+                .map_err(|e| CredentialError::Cbor(e.to_string())),
+            _ => Err(malformed("tag 24 must wrap a byte string")),
+        },
+        Cbor::Map(_) => Ok(item.clone()),
+        _ => Err(malformed("issuer signed item must be a map")),
+    }
+}
+
+fn get<'a>(value: &'a Cbor, key: &str) -> Option<&'a Cbor> {
+    let Cbor::Map(entries) = value else {
+        return None;
+    };
+    entries
+        .iter()
+        .find(|(k, _)| matches!(k, Cbor::Text(k) if k == key))
+        .map(|(_, v)| v)
+}
+
+fn json(value: &Cbor) -> Result<Value, CredentialError> {
+    Ok(match value {
+        Cbor::Null => Value::Null,
+        Cbor::Bool(b) => Value::Bool(*b),
+        Cbor::Text(text) => Value::String(text.clone()),
+        Cbor::Integer(integer) => {
+            let integer = i128::from(*integer);
+            if let Ok(n) = i64::try_from(integer) {
+                Value::from(n)
+            } else if let Ok(n) = u64::try_from(integer) {
+                Value::from(n)
+            } else {
+                Value::String(integer.to_string())
+            }
+        }
+        Cbor::Float(f) => Number::from_f64(*f).map_or(Value::Null, Value::Number),
+        Cbor::Bytes(bytes) => Value::String(base64::prelude::BASE64_STANDARD.encode(bytes)),
+        Cbor::Tag(TAG_TDATE | TAG_FULL_DATE, inner) => match inner.as_ref() {
+            Cbor::Text(date) => Value::String(date.clone()),
+            _ => return Err(malformed("date tags must wrap text")),
+        },
+        Cbor::Tag(TAG_EPOCH, inner) => {
+            let seconds = match inner.as_ref() {
+                Cbor::Integer(i) => i64::try_from(i128::from(*i)).ok(),
+                Cbor::Float(f) => Some(f.floor() as i64),
+                _ => None,
+            };
+            let date = seconds
+                .and_then(|s| chrono::DateTime::from_timestamp(s, 0))
+                .ok_or_else(|| malformed("invalid epoch date"))?;
+            Value::String(date.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        }
+        Cbor::Tag(TAG_ENCODED_CBOR, _) => json(&encoded(value)?)?,
+        Cbor::Tag(_, inner) => json(inner)?,
+        Cbor::Array(items) => Value::Array(items.iter().map(json).collect::<Result<_, _>>()?),
+        Cbor::Map(entries) => {
+            let mut object = Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    Cbor::Text(key) => key.clone(),
+                    Cbor::Integer(i) => i128::from(*i).to_string(),
+                    _ => return Err(malformed("map keys must be text or integers")),
+                };
+                object.insert(key, json(value)?);
+            }
+            Value::Object(object)
+        }
+        _ => return Err(malformed("unsupported CBOR value")),
+    })
+}
+
+fn malformed(reason: &str) -> CredentialError {
+    CredentialError::Malformed(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value as Cbor;
+    use serde_json::json;
+
+    use super::{decode_mdoc, MDL_NAMESPACE};
+    use crate::{builder::OcaBuilder, credential::Credential};
+
+    fn text(s: &str) -> Cbor {
+        Cbor::Text(s.to_string())
+    }
+
+    fn item(digest_id: i64, identifier: &str, value: Cbor) -> Cbor {
+        let item = Cbor::Map(vec![
+            (text("digestID"), Cbor::Integer(digest_id.into())),
+            (text("random"), Cbor::Bytes(vec![7; 16])),
+            (text("elementIdentifier"), text(identifier)),
+            (text("elementValue"), value),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&item, &mut bytes).unwrap();
+        Cbor::Tag(24, Box::new(Cbor::Bytes(bytes)))
+    }
+
+    fn device_response() -> Vec<u8> {
+        let items = vec![
+            item(0, "family_name", text("Mustermann")),
+            item(1, "given_name", text("Erika")),
+            item(
+                2,
+                "birth_date",
+                Cbor::Tag(1004, Box::new(text("1964-08-12"))),
+            ),
+            item(
+                3,
+                "issue_date",
+                Cbor::Tag(0, Box::new(text("2024-01-01T00:00:00Z"))),
+            ),
+            item(4, "portrait", Cbor::Bytes(vec![0xff, 0xd8, 0xff, 0xe0])),
+            item(
+                5,
+                "driving_privileges",
+                Cbor::Array(vec![Cbor::Map(vec![
+                    (text("vehicle_category_code"), text("B")),
+                    (
+                        text("issue_date"),
+                        Cbor::Tag(1004, Box::new(text("2010-03-01"))),
+                    ),
+                ])]),
+            ),
+        ];
+        let issuer_signed = Cbor::Map(vec![
+            (
+                text("nameSpaces"),
+                Cbor::Map(vec![(text(MDL_NAMESPACE), Cbor::Array(items))]),
+            ),
+            (text("issuerAuth"), Cbor::Array(vec![])),
+        ]);
+        let response = Cbor::Map(vec![
+            (text("version"), text("1.0")),
+            (
+                text("documents"),
+                Cbor::Array(vec![Cbor::Map(vec![
+                    (text("docType"), text("org.iso.18013.5.1.mDL")),
+                    (text("issuerSigned"), issuer_signed),
+                ])]),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&response, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            decode_mdoc(&device_response()).unwrap(),
+            json!({
+                "org.iso.18013.5.1": {
+                    "family_name": "Mustermann",
+                    "given_name": "Erika",
+                    "birth_date": "1964-08-12",
+                    "issue_date": "2024-01-01T00:00:00Z",
+                    "portrait": "/9j/4A==",
+                    "driving_privileges": [
+                        { "vehicle_category_code": "B", "issue_date": "2010-03-01" }
+                    ]
+                }
+            })
+        );
+        assert!(decode_mdoc(b"\xa1\x61a").is_err());
+    }
+
+    #[test]
+    fn mapping() {
+        let oca = OcaBuilder::new()
+            .add_attribute("surname", "Text")
+            .add_attribute("birthDate", "DateTime")
+            .add_mapping("surname", "org.iso.18013.5.1.family_name")
+            .add_mapping("birthDate", "org.iso.18013.5.1.birth_date")
+            .build()
+            .unwrap();
+        let credential = Credential::decode_mdoc(&device_response()).unwrap();
+        assert_eq!(
+            credential.attributes(&oca).unwrap(),
+            json!({ "surname": "Mustermann", "birthDate": "1964-08-12" })
+        );
+    }
+}
@@ -6,15 +6,24 @@
 
 use std::collections::BTreeMap;
 
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 
-use crate::impl_said;
+use crate::{
+    impl_said, impl_typed_overlay,
+    overlays::{unversioned_type, Overlays, TypedOverlay},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Oca {
     pub(crate) capture_base: CaptureBase,
-    pub(crate) overlays: Vec<(String, OcaLayer)>,
+    pub(crate) overlays: Overlays,
+    /// The bundles referenced through `refs:` attributes, by capture base digest.
+    ///
+    /// Only the root bundle holds dependencies, including those of its
+    /// dependencies.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) dependencies: BTreeMap<String, Oca>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,9 +31,111 @@ pub struct CaptureBase {
     r#type: String,
     pub(crate) digest: String,
     pub(crate) classification: Option<String>,
-    pub(crate) attributes: BTreeMap<String, String>,
+    pub(crate) attributes: BTreeMap<String, AttributeType>,
     pub(crate) flagged_attributes: Vec<String>,
+    /// Fields not covered by the model, kept so the SAID survives a round trip.
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+    /// The document the capture base was parsed from.
+    #[serde(skip)]
+    pub(crate) source: Option<Value>,
 }
+/// The type of a capture base attribute, serialized as the spec string such as
+/// `Text`, `refs:<digest>` or `Array[DateTime]`.
+///
+/// Types this crate does not know are kept verbatim in [`AttributeType::Other`],
+/// so the capture base digest survives a round trip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeType {
+    Text,
+    Numeric,
+    Boolean,
+    DateTime,
+    Binary,
+    /// A nested object described by the capture base with this digest.
+    Reference(String),
+    Array(Box<AttributeType>),
+    Other(String),
+}
+
+impl AttributeType {
+    /// The capture base digest this type refers to, also through arrays.
+    pub fn reference(&self) -> Option<&str> {
+        match self {
+            AttributeType::Reference(digest) => Some(digest),
+            AttributeType::Array(inner) => inner.reference(),
+            _ => None,
+        }
+    }
+    /// The element type for arrays, the type itself otherwise.
+    pub fn element_type(&self) -> &AttributeType {
+        match self {
+            AttributeType::Array(inner) => inner.element_type(),
+            other => other,
+        }
+    }
+    pub fn is_array(&self) -> bool {
+        matches!(self, AttributeType::Array(_))
+    }
+}
+
+impl std::fmt::Display for AttributeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeType::Text => f.write_str("Text"),
+            AttributeType::Numeric => f.write_str("Numeric"),
+            AttributeType::Boolean => f.write_str("Boolean"),
+            AttributeType::DateTime => f.write_str("DateTime"),
+            AttributeType::Binary => f.write_str("Binary"),
+            AttributeType::Reference(digest) => write!(f, "refs:{digest}"),
+            AttributeType::Array(inner) => write!(f, "Array[{inner}]"),
+            AttributeType::Other(other) => f.write_str(other),
+        }
+    }
+}
+
+impl From<&str> for AttributeType {
+    fn from(value: &str) -> Self {
+        match value {
+            "Text" => AttributeType::Text,
+            "Numeric" => AttributeType::Numeric,
+            "Boolean" => AttributeType::Boolean,
+            "DateTime" => AttributeType::DateTime,
+            "Binary" => AttributeType::Binary,
+            _ => {
+                if let Some(digest) = value.strip_prefix("refs:") {
+                    AttributeType::Reference(digest.to_string())
+                } else if let Some(inner) = value
+                    .strip_prefix("Array[")
+                    .and_then(|inner| inner.strip_suffix(']'))
+                {
+                    AttributeType::Array(Box::new(inner.into()))
+                } else {
+                    AttributeType::Other(value.to_string())
+                }
+            }
+        }
+    }
+}
+
+impl From<String> for AttributeType {
+    fn from(value: String) -> Self {
+        value.as_str().into()
+    }
+}
+
+impl Serialize for AttributeType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AttributeType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(String::deserialize(deserializer)?.into())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CharacterEncoding {
     capture_base: String,
@@ -32,8 +143,10 @@ pub struct CharacterEncoding {
     r#type: String,
     default_character_encoding: Encoding,
     attribute_character_encoding: BTreeMap<String, Encoding>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Encoding {
     #[serde(rename = "utf-8")]
     Utf8,
@@ -46,8 +159,10 @@ pub struct Conformance {
     digest: String,
     r#type: String,
     attribute_conformance: BTreeMap<String, ConformancePolicy>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConformancePolicy {
     M,
     O,
@@ -62,6 +177,8 @@ pub struct Label {
     attribute_labels: BTreeMap<String, String>,
     attribute_categories: Vec<String>,
     category_labels: BTreeMap<String, String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -70,6 +187,8 @@ pub struct Format {
     digest: String,
     r#type: String,
     attribute_formats: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -78,16 +197,21 @@ pub struct Style {
     digest: String,
     r#type: String,
     style_json: StyleJson,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct StyleJson {
-    pub(crate) title: String,
-    pub(crate) subtitle: String,
-    pub(crate) card_color: u64,
-    pub(crate) text_color: String,
-    pub(crate) background_card: Option<String>,
-    pub(crate) ordered_properties: Vec<String>,
+    pub title: String,
+    pub subtitle: String,
+    pub card_color: u64,
+    pub text_color: String,
+    pub background_card: Option<String>,
+    pub ordered_properties: Vec<String>,
+    /// Style properties not covered by the model.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -96,37 +220,110 @@ pub struct AttributeMapping {
     digest: String,
     r#type: String,
     attribute_mapping: BTreeMap<String, String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
-impl AttributeMapping {
-    pub fn map_json(&self, data: &Value) -> Value {
-        let mut new_object = serde_json::Map::new();
-        let old_object_selector = &mut jsonpath_lib::selector(data);
-        for (key, mapped_key) in &self.attribute_mapping {
-            let Ok(val) = old_object_selector(&format!("$.{mapped_key}")) else {
-                println!("selector failed {mapped_key}");
-                continue;
-            };
-            let Some(first_val) = val.first() else {
-                println!("nothing found at {mapped_key}");
-                continue;
-            };
-            if key != "$" {
-                new_object.insert(key.to_string(), (*first_val).clone());
-                continue;
-            }
-            // for now only remap objects
-            if let Value::Object(inner) = first_val {
-                for (inner_key, inner_val) in inner {
-                    new_object.insert(inner_key.to_string(), inner_val.clone());
-                }
-            }
-        }
-        Value::Object(new_object)
-    }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Meta {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    language: String,
+    name: String,
+    description: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Information {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    language: String,
+    attribute_information: BTreeMap<String, String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    language: String,
+    attribute_entries: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntryCode {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    attribute_entry_codes: BTreeMap<String, Vec<String>>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Unit {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    metric_system: String,
+    attribute_units: BTreeMap<String, String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cardinality {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    attribute_cardinality: BTreeMap<String, String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Standard {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    attribute_standards: BTreeMap<String, String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Sensitive {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    attributes: Vec<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Subset {
+    capture_base: String,
+    digest: String,
+    r#type: String,
+    attributes: Vec<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// An overlay of a bundle.
+///
+/// Deserialization picks the model from the overlay's `type` field. Overlays of
+/// unknown types, or of an unsupported major version, are kept as [`OcaLayer::Other`].
+#[derive(Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum OcaLayer {
     CharacterEncoding(CharacterEncoding),
@@ -135,9 +332,109 @@ pub enum OcaLayer {
     Format(Format),
     Style(Style),
     AttributeMapping(AttributeMapping),
+    Meta(Meta),
+    Information(Information),
+    Entry(Entry),
+    EntryCode(EntryCode),
+    Unit(Unit),
+    Cardinality(Cardinality),
+    Standard(Standard),
+    Sensitive(Sensitive),
+    Subset(Subset),
     Other(Value),
 }
 
+impl<'de> Deserialize<'de> for OcaLayer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        OcaLayer::from_value(value).map_err(de::Error::custom)
+    }
+}
+
+impl OcaLayer {
+    /// Builds the overlay model matching the `type` field of `value`.
+    pub fn from_value(value: Value) -> Result<Self, String> {
+        fn typed<T: DeserializeOwned>(
+            value: Value,
+            overlay_type: &str,
+            wrap: fn(T) -> OcaLayer,
+        ) -> Result<OcaLayer, String> {
+            serde_json::from_value(value)
+                .map(wrap)
+                .map_err(|e| format!("invalid {overlay_type} overlay: {e}"))
+        }
+        let Value::Object(object) = &value else {
+            return Err(format!("overlay must be a JSON object, got {value}"));
+        };
+        let Some(overlay_type) = object.get("type").and_then(Value::as_str) else {
+            return Ok(OcaLayer::Other(value));
+        };
+        let overlay_type = overlay_type.to_string();
+        let base_type = unversioned_type(&overlay_type);
+        let supported_version = overlay_type
+            .strip_prefix(base_type)
+            .and_then(|version| version.strip_prefix('/'))
+            .is_none_or(|version| version.split('.').next() == Some("1"));
+        if !supported_version {
+            return Ok(OcaLayer::Other(value));
+        }
+        match base_type {
+            t if t == CharacterEncoding::OVERLAY_TYPE => {
+                typed(value, &overlay_type, OcaLayer::CharacterEncoding)
+            }
+            t if t == Label::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Label),
+            t if t == Conformance::OVERLAY_TYPE => {
+                typed(value, &overlay_type, OcaLayer::Conformance)
+            }
+            t if t == Format::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Format),
+            t if t == Style::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Style),
+            t if t == AttributeMapping::OVERLAY_TYPE => {
+                typed(value, &overlay_type, OcaLayer::AttributeMapping)
+            }
+            t if t == Meta::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Meta),
+            t if t == Information::OVERLAY_TYPE => {
+                typed(value, &overlay_type, OcaLayer::Information)
+            }
+            t if t == Entry::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Entry),
+            t if t == EntryCode::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::EntryCode),
+            t if t == Unit::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Unit),
+            t if t == Cardinality::OVERLAY_TYPE => {
+                typed(value, &overlay_type, OcaLayer::Cardinality)
+            }
+            t if t == Standard::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Standard),
+            t if t == Sensitive::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Sensitive),
+            t if t == Subset::OVERLAY_TYPE => typed(value, &overlay_type, OcaLayer::Subset),
+            _ => Ok(OcaLayer::Other(value)),
+        }
+    }
+}
+
+/// Evaluates `$typed` with `$overlay` bound to the typed overlay in `$layer`,
+/// or `$other` with `$value` bound to the JSON of an unknown overlay.
+#[macro_export]
+macro_rules! match_layer {
+    ($layer:expr, $overlay:ident => $typed:expr, $value:ident => $other:expr) => {
+        match $layer {
+            $crate::models::OcaLayer::CharacterEncoding($overlay) => $typed,
+            $crate::models::OcaLayer::Label($overlay) => $typed,
+            $crate::models::OcaLayer::Conformance($overlay) => $typed,
+            $crate::models::OcaLayer::Format($overlay) => $typed,
+            $crate::models::OcaLayer::Style($overlay) => $typed,
+            $crate::models::OcaLayer::AttributeMapping($overlay) => $typed,
+            $crate::models::OcaLayer::Meta($overlay) => $typed,
+            $crate::models::OcaLayer::Information($overlay) => $typed,
+            $crate::models::OcaLayer::Entry($overlay) => $typed,
+            $crate::models::OcaLayer::EntryCode($overlay) => $typed,
+            $crate::models::OcaLayer::Unit($overlay) => $typed,
+            $crate::models::OcaLayer::Cardinality($overlay) => $typed,
+            $crate::models::OcaLayer::Standard($overlay) => $typed,
+            $crate::models::OcaLayer::Sensitive($overlay) => $typed,
+            $crate::models::OcaLayer::Subset($overlay) => $typed,
+            $crate::models::OcaLayer::Other($value) => $other,
+        }
+    };
+}
+
 impl Conformance {
     pub fn new(
         capture_base: &str,
@@ -148,22 +445,388 @@ impl Conformance {
             r#type: "spec/overlays/conformance/1.0".to_string(),
             digest: "".to_string(),
             attribute_conformance,
+            extra: Map::new(),
         }
     }
 }
 impl CaptureBase {
-    pub fn new(attributes: BTreeMap<String, String>, flagged_attributes: Vec<String>) -> Self {
+    pub fn new(
+        attributes: BTreeMap<String, AttributeType>,
+        flagged_attributes: Vec<String>,
+    ) -> Self {
         Self {
             r#type: "spec/capture_base/1.0".into(),
             digest: "".into(),
             classification: Some("GICS:45102010".into()),
             attributes,
             flagged_attributes,
+            extra: Map::new(),
+            source: None,
         }
     }
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+    pub fn classification(&self) -> Option<&str> {
+        self.classification.as_deref()
+    }
+    pub fn attributes(&self) -> &BTreeMap<String, AttributeType> {
+        &self.attributes
+    }
+    pub fn attribute_type(&self, attribute: &str) -> Option<&AttributeType> {
+        self.attributes.get(attribute)
+    }
+    pub fn flagged_attributes(&self) -> &[String] {
+        &self.flagged_attributes
+    }
+    pub fn is_flagged(&self, attribute: &str) -> bool {
+        self.flagged_attributes.iter().any(|a| a == attribute)
+    }
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+}
+
+impl Oca {
+    pub fn capture_base(&self) -> &CaptureBase {
+        &self.capture_base
+    }
+    pub fn overlays(&self) -> &Overlays {
+        &self.overlays
+    }
+    pub fn attributes(&self) -> &BTreeMap<String, AttributeType> {
+        &self.capture_base.attributes
+    }
+    /// The label overlay for exactly `language`.
+    pub fn label(&self, language: &str) -> Option<&Label> {
+        self.overlays.for_exact_language(language)
+    }
+    /// The label overlay best matching `language`, see [`Overlays::for_language`].
+    pub fn label_for_language(&self, language: &str) -> Option<&Label> {
+        self.overlays.for_language(language)
+    }
+    /// The entry overlay best matching `language`, see [`Overlays::for_language`].
+    pub fn entry_for_language(&self, language: &str) -> Option<&Entry> {
+        self.overlays.for_language(language)
+    }
+    /// The label of entry `code` of `attribute` in `language`, or `code` itself
+    /// if there is none.
+    pub fn localized_entry<'a>(
+        &'a self,
+        attribute: &str,
+        code: &'a str,
+        language: &str,
+    ) -> &'a str {
+        self.entry_for_language(language)
+            .and_then(|entry| entry.entry_for(attribute, code))
+            .unwrap_or(code)
+    }
+    pub fn style(&self) -> Option<&StyleJson> {
+        self.overlays
+            .first_of::<Style>()
+            .map(|style| &style.style_json)
+    }
+    pub fn format_for(&self, attribute: &str) -> Option<&str> {
+        self.overlays
+            .overlays_of::<Format>()
+            .find_map(|format| format.format_for(attribute))
+    }
+    pub fn character_encoding_for(&self, attribute: &str) -> Option<Encoding> {
+        self.overlays
+            .first_of::<CharacterEncoding>()
+            .map(|encoding| encoding.encoding_for(attribute))
+    }
+    pub fn unit_for(&self, attribute: &str) -> Option<&str> {
+        self.overlays
+            .overlays_of::<Unit>()
+            .find_map(|unit| unit.unit_for(attribute))
+    }
+    /// Whether `attribute` is flagged in the capture base or listed in a
+    /// sensitive overlay.
+    pub fn is_sensitive(&self, attribute: &str) -> bool {
+        self.capture_base.is_flagged(attribute)
+            || self
+                .overlays
+                .overlays_of::<Sensitive>()
+                .any(|sensitive| sensitive.attributes.iter().any(|a| a == attribute))
+    }
+    pub fn conformance_for(&self, attribute: &str) -> Option<ConformancePolicy> {
+        self.overlays
+            .overlays_of::<Conformance>()
+            .find_map(|conformance| conformance.attribute_conformance.get(attribute).copied())
+    }
+    pub fn attribute_mapping(&self) -> Option<&AttributeMapping> {
+        self.overlays.first_of()
+    }
+    pub fn dependencies(&self) -> &BTreeMap<String, Oca> {
+        &self.dependencies
+    }
+    /// The bundle with capture base `digest`, this one or one of its dependencies.
+    pub fn bundle(&self, digest: &str) -> Option<&Oca> {
+        if self.capture_base.digest == digest {
+            Some(self)
+        } else {
+            self.dependencies.get(digest)
+        }
+    }
+    /// Adds `dependency` and its own dependencies to the bundles this one can refer to.
+    pub fn add_dependency(&mut self, mut dependency: Oca) {
+        let nested = std::mem::take(&mut dependency.dependencies);
+        self.dependencies
+            .insert(dependency.capture_base.digest.clone(), dependency);
+        self.dependencies.extend(nested);
+    }
+    /// The bundle `attribute` of this bundle's capture base refers to.
+    pub fn referenced_bundle(&self, attribute: &str) -> Option<&Oca> {
+        self.attributes()
+            .get(attribute)
+            .and_then(AttributeType::reference)
+            .and_then(|digest| self.bundle(digest))
+    }
+    /// The capture bases every capture base of this bundle refers to, by digest.
+    pub fn dependency_graph(&self) -> BTreeMap<&str, Vec<&str>> {
+        std::iter::once(self)
+            .chain(self.dependencies.values())
+            .map(|bundle| {
+                let references = bundle
+                    .attributes()
+                    .values()
+                    .filter_map(AttributeType::reference)
+                    .collect();
+                (bundle.capture_base.digest.as_str(), references)
+            })
+            .collect()
+    }
+}
+
+impl CharacterEncoding {
+    pub fn capture_base(&self) -> &str {
+        &self.capture_base
+    }
+    pub fn default_character_encoding(&self) -> Encoding {
+        self.default_character_encoding
+    }
+    pub fn attribute_character_encoding(&self) -> &BTreeMap<String, Encoding> {
+        &self.attribute_character_encoding
+    }
+    pub fn encoding_for(&self, attribute: &str) -> Encoding {
+        self.attribute_character_encoding
+            .get(attribute)
+            .copied()
+            .unwrap_or(self.default_character_encoding)
+    }
+}
+
+impl Conformance {
+    pub fn capture_base(&self) -> &str {
+        &self.capture_base
+    }
+    pub fn attribute_conformance(&self) -> &BTreeMap<String, ConformancePolicy> {
+        &self.attribute_conformance
+    }
+}
+
+impl Label {
+    pub fn capture_base(&self) -> &str {
+        &self.capture_base
+    }
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn attribute_labels(&self) -> &BTreeMap<String, String> {
+        &self.attribute_labels
+    }
+    pub fn label_for(&self, attribute: &str) -> Option<&str> {
+        self.attribute_labels.get(attribute).map(String::as_str)
+    }
+    pub fn attribute_categories(&self) -> &[String] {
+        &self.attribute_categories
+    }
+    pub fn category_labels(&self) -> &BTreeMap<String, String> {
+        &self.category_labels
+    }
+}
+
+impl Format {
+    pub fn capture_base(&self) -> &str {
+        &self.capture_base
+    }
+    pub fn attribute_formats(&self) -> &BTreeMap<String, String> {
+        &self.attribute_formats
+    }
+    pub fn format_for(&self, attribute: &str) -> Option<&str> {
+        self.attribute_formats.get(attribute).map(String::as_str)
+    }
+}
+
+impl Style {
+    pub fn capture_base(&self) -> &str {
+        &self.capture_base
+    }
+    pub fn style_json(&self) -> &StyleJson {
+        &self.style_json
+    }
+}
+
+impl AttributeMapping {
+    pub fn capture_base(&self) -> &str {
+        &self.capture_base
+    }
+    pub fn attribute_mapping(&self) -> &BTreeMap<String, String> {
+        &self.attribute_mapping
+    }
+}
+
+impl Meta {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl Information {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn attribute_information(&self) -> &BTreeMap<String, String> {
+        &self.attribute_information
+    }
+}
+
+impl Entry {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn attribute_entries(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        &self.attribute_entries
+    }
+    /// The localized label of entry `code` of `attribute`.
+    pub fn entry_for(&self, attribute: &str, code: &str) -> Option<&str> {
+        self.attribute_entries
+            .get(attribute)?
+            .get(code)
+            .map(String::as_str)
+    }
+}
+
+impl EntryCode {
+    pub fn attribute_entry_codes(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.attribute_entry_codes
+    }
+    pub fn codes_for(&self, attribute: &str) -> Option<&[String]> {
+        self.attribute_entry_codes.get(attribute).map(Vec::as_slice)
+    }
+}
+
+impl Unit {
+    pub fn metric_system(&self) -> &str {
+        &self.metric_system
+    }
+    pub fn attribute_units(&self) -> &BTreeMap<String, String> {
+        &self.attribute_units
+    }
+    pub fn unit_for(&self, attribute: &str) -> Option<&str> {
+        self.attribute_units.get(attribute).map(String::as_str)
+    }
+}
+
+impl Cardinality {
+    pub fn attribute_cardinality(&self) -> &BTreeMap<String, String> {
+        &self.attribute_cardinality
+    }
+}
+
+impl Standard {
+    pub fn attribute_standards(&self) -> &BTreeMap<String, String> {
+        &self.attribute_standards
+    }
+}
+
+impl Sensitive {
+    pub fn attributes(&self) -> &[String] {
+        &self.attributes
+    }
+}
+
+impl Subset {
+    pub fn attributes(&self) -> &[String] {
+        &self.attributes
+    }
 }
 
 impl OcaLayer {
+    pub fn overlay_type(&self) -> &str {
+        match_layer!(self, overlay => &overlay.r#type, value => value
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or(""))
+    }
+    /// The digest of the capture base this overlay is bound to.
+    pub fn capture_base(&self) -> &str {
+        match_layer!(self, overlay => &overlay.capture_base, value => value
+            .get("capture_base")
+            .and_then(Value::as_str)
+            .unwrap_or(""))
+    }
+    /// Fields of a typed overlay that are not covered by its model.
+    pub fn extra(&self) -> Option<&Map<String, Value>> {
+        match_layer!(self, overlay => Some(&overlay.extra), _value => None)
+    }
+    /// The language of language specific overlays such as labels.
+    pub fn language(&self) -> Option<&str> {
+        match self {
+            OcaLayer::Label(label) => Some(&label.language),
+            OcaLayer::Meta(meta) => Some(&meta.language),
+            OcaLayer::Information(information) => Some(&information.language),
+            OcaLayer::Entry(entry) => Some(&entry.language),
+            OcaLayer::Other(value) => value.get("language").and_then(Value::as_str),
+            _ => None,
+        }
+    }
+    /// The capture base attributes this overlay refers to.
+    pub fn attribute_names(&self) -> Vec<&str> {
+        fn keys<V>(map: &BTreeMap<String, V>) -> Vec<&str> {
+            map.keys().map(String::as_str).collect()
+        }
+        match self {
+            OcaLayer::CharacterEncoding(character_encoding) => {
+                keys(&character_encoding.attribute_character_encoding)
+            }
+            OcaLayer::Label(label) => keys(&label.attribute_labels),
+            OcaLayer::Conformance(conformance) => keys(&conformance.attribute_conformance),
+            OcaLayer::Format(format) => keys(&format.attribute_formats),
+            OcaLayer::Style(style) => style
+                .style_json
+                .ordered_properties
+                .iter()
+                .map(String::as_str)
+                .collect(),
+            // `$` maps a whole object onto the capture base
+            OcaLayer::AttributeMapping(attribute_mapping) => attribute_mapping
+                .attribute_mapping
+                .keys()
+                .map(String::as_str)
+                .filter(|key| *key != "$")
+                .collect(),
+            OcaLayer::Meta(..) => vec![],
+            OcaLayer::Information(information) => keys(&information.attribute_information),
+            OcaLayer::Entry(entry) => keys(&entry.attribute_entries),
+            OcaLayer::EntryCode(entry_code) => keys(&entry_code.attribute_entry_codes),
+            OcaLayer::Unit(unit) => keys(&unit.attribute_units),
+            OcaLayer::Cardinality(cardinality) => keys(&cardinality.attribute_cardinality),
+            OcaLayer::Standard(standard) => keys(&standard.attribute_standards),
+            OcaLayer::Sensitive(sensitive) => {
+                sensitive.attributes.iter().map(String::as_str).collect()
+            }
+            OcaLayer::Subset(subset) => subset.attributes.iter().map(String::as_str).collect(),
+            OcaLayer::Other(..) => vec![],
+        }
+    }
     pub fn new_label_layer(
         capture_base: &str,
         language: &str,
@@ -179,6 +842,7 @@ impl OcaLayer {
             attribute_labels,
             attribute_categories,
             category_labels,
+            extra: Map::new(),
         })
     }
     pub fn new_style_layer(capture_base: &str, style_json: StyleJson) -> Self {
@@ -187,6 +851,7 @@ impl OcaLayer {
             digest: "".into(),
             r#type: "spec/overlays/style/1.0".into(),
             style_json,
+            extra: Map::new(),
         })
     }
     pub fn new_format_layer(
@@ -198,6 +863,7 @@ impl OcaLayer {
             digest: "".into(),
             r#type: "spec/overlays/format/1.0".into(),
             attribute_formats,
+            extra: Map::new(),
         })
     }
     pub fn new_character_encoding(
@@ -210,6 +876,7 @@ impl OcaLayer {
             r#type: "spec/overlays/character_encoding/1.0".into(),
             default_character_encoding: Encoding::Utf8,
             attribute_character_encoding,
+            extra: Map::new(),
         })
     }
     pub fn new_attribute_mapping_layer(
@@ -221,22 +888,157 @@ impl OcaLayer {
             digest: "".to_string(),
             r#type: "spec/overlays/attribute_mapping/1.0".to_string(),
             attribute_mapping,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_meta_layer(
+        capture_base: &str,
+        language: &str,
+        name: &str,
+        description: &str,
+    ) -> Self {
+        Self::Meta(Meta {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/meta/1.0".into(),
+            language: language.into(),
+            name: name.into(),
+            description: description.into(),
+            extra: Map::new(),
+        })
+    }
+    pub fn new_information_layer(
+        capture_base: &str,
+        language: &str,
+        attribute_information: BTreeMap<String, String>,
+    ) -> Self {
+        Self::Information(Information {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/information/1.0".into(),
+            language: language.into(),
+            attribute_information,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_entry_layer(
+        capture_base: &str,
+        language: &str,
+        attribute_entries: BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Self {
+        Self::Entry(Entry {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/entry/1.0".into(),
+            language: language.into(),
+            attribute_entries,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_entry_code_layer(
+        capture_base: &str,
+        attribute_entry_codes: BTreeMap<String, Vec<String>>,
+    ) -> Self {
+        Self::EntryCode(EntryCode {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/entry_code/1.0".into(),
+            attribute_entry_codes,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_unit_layer(
+        capture_base: &str,
+        metric_system: &str,
+        attribute_units: BTreeMap<String, String>,
+    ) -> Self {
+        Self::Unit(Unit {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/unit/1.0".into(),
+            metric_system: metric_system.into(),
+            attribute_units,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_cardinality_layer(
+        capture_base: &str,
+        attribute_cardinality: BTreeMap<String, String>,
+    ) -> Self {
+        Self::Cardinality(Cardinality {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/cardinality/1.0".into(),
+            attribute_cardinality,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_standard_layer(
+        capture_base: &str,
+        attribute_standards: BTreeMap<String, String>,
+    ) -> Self {
+        Self::Standard(Standard {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/standard/1.0".into(),
+            attribute_standards,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_sensitive_layer(capture_base: &str, attributes: Vec<String>) -> Self {
+        Self::Sensitive(Sensitive {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/sensitive/1.0".into(),
+            attributes,
+            extra: Map::new(),
+        })
+    }
+    pub fn new_subset_layer(capture_base: &str, attributes: Vec<String>) -> Self {
+        Self::Subset(Subset {
+            capture_base: capture_base.into(),
+            digest: "".into(),
+            r#type: "spec/overlays/subset/1.0".into(),
+            attributes,
+            extra: Map::new(),
         })
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StyleJsonFile {
-    pub(crate) attributes: BTreeMap<String, Attribute>,
-    pub(crate) style: StyleJson,
+    pub attributes: BTreeMap<String, Attribute>,
+    pub style: StyleJson,
+    /// Title and subtitle by language.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, StyleTranslation>,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Attribute {
-    pub(crate) display_name: String,
-    pub(crate) field_type: AttributeFieldType,
+    /// The display name for languages missing from `display_names`.
+    pub display_name: String,
+    pub field_type: AttributeFieldType,
+    /// Display names by language.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub display_names: BTreeMap<String, String>,
+}
+impl Attribute {
+    pub fn display_name_for(&self, language: &str) -> &str {
+        self.display_names
+            .get(language)
+            .unwrap_or(&self.display_name)
+    }
 }
-#[derive(Serialize, Deserialize, Debug)]
+/// A localized title and subtitle, falling back to the ones of the style.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct StyleTranslation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtitle: Option<String>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum AttributeFieldType {
     String,
@@ -257,12 +1059,38 @@ impl_said!(Conformance);
 impl_said!(Format);
 impl_said!(Style);
 impl_said!(AttributeMapping);
+impl_said!(Meta);
+impl_said!(Information);
+impl_said!(Entry);
+impl_said!(EntryCode);
+impl_said!(Unit);
+impl_said!(Cardinality);
+impl_said!(Standard);
+impl_said!(Sensitive);
+impl_said!(Subset);
+
+impl_typed_overlay!(CharacterEncoding, "spec/overlays/character_encoding");
+impl_typed_overlay!(Label, "spec/overlays/label");
+impl_typed_overlay!(Conformance, "spec/overlays/conformance");
+impl_typed_overlay!(Format, "spec/overlays/format");
+impl_typed_overlay!(Style, "spec/overlays/style");
+impl_typed_overlay!(AttributeMapping, "spec/overlays/attribute_mapping");
+impl_typed_overlay!(Meta, "spec/overlays/meta");
+impl_typed_overlay!(Information, "spec/overlays/information");
+impl_typed_overlay!(Entry, "spec/overlays/entry");
+impl_typed_overlay!(EntryCode, "spec/overlays/entry_code");
+impl_typed_overlay!(Unit, "spec/overlays/unit");
+impl_typed_overlay!(Cardinality, "spec/overlays/cardinality");
+impl_typed_overlay!(Standard, "spec/overlays/standard");
+impl_typed_overlay!(Sensitive, "spec/overlays/sensitive");
+impl_typed_overlay!(Subset, "spec/overlays/subset");
 
 #[cfg(test)]
 mod test {
-    use serde_json::Value;
+    use serde_json::{json, Value};
 
-    use super::AttributeMapping;
+    use super::{AttributeMapping, AttributeType, OcaLayer};
+    use crate::builder::OcaBuilder;
 
     #[test]
     fn test_mapping() {
@@ -272,4 +1100,77 @@ mod test {
         let new_val = mapping_layer.map_json(&value);
         println!("{new_val}");
     }
+
+    #[test]
+    fn layer_dispatch_on_type() {
+        let layer = |value: Value| serde_json::from_value::<OcaLayer>(value);
+        let subset = json!({
+            "capture_base": "E", "digest": "E", "type": "spec/overlays/subset/1.0",
+            "attributes": ["a"]
+        });
+        assert!(matches!(layer(subset), Ok(OcaLayer::Subset(_))));
+        let label = json!({
+            "capture_base": "E", "digest": "E", "type": "spec/overlays/label/1.1",
+            "language": "en", "attribute_labels": {}, "attribute_categories": [],
+            "category_labels": {}
+        });
+        assert!(matches!(layer(label), Ok(OcaLayer::Label(_))));
+        let future = json!({"capture_base": "E", "type": "spec/overlays/label/2.0"});
+        assert!(matches!(layer(future), Ok(OcaLayer::Other(_))));
+        let unknown = json!({"capture_base": "E", "type": "spec/overlays/layout/1.0"});
+        assert!(matches!(layer(unknown), Ok(OcaLayer::Other(_))));
+
+        let broken = json!({
+            "capture_base": "E", "digest": "E", "type": "spec/overlays/format/1.0"
+        });
+        let error = layer(broken).unwrap_err().to_string();
+        assert!(
+            error.contains("invalid spec/overlays/format/1.0 overlay"),
+            "{error}"
+        );
+        assert!(error.contains("attribute_formats"), "{error}");
+    }
+
+    #[test]
+    fn attribute_types() {
+        for (text, attribute_type) in [
+            ("Numeric", AttributeType::Numeric),
+            ("refs:EAbc", AttributeType::Reference("EAbc".into())),
+            (
+                "Array[Array[refs:EAbc]]",
+                AttributeType::Array(Box::new(AttributeType::Array(Box::new(
+                    AttributeType::Reference("EAbc".into()),
+                )))),
+            ),
+            (
+                "Array[Binary]",
+                AttributeType::Array(Box::new(AttributeType::Binary)),
+            ),
+            ("Decimal", AttributeType::Other("Decimal".into())),
+        ] {
+            assert_eq!(AttributeType::from(text), attribute_type);
+            assert_eq!(serde_json::to_value(&attribute_type).unwrap(), json!(text));
+            let parsed: AttributeType = serde_json::from_value(json!(text)).unwrap();
+            assert_eq!(parsed, attribute_type);
+        }
+        let nested = AttributeType::from("Array[Array[refs:EAbc]]");
+        assert_eq!(nested.reference(), Some("EAbc"));
+        assert_eq!(
+            nested.element_type(),
+            &AttributeType::Reference("EAbc".into())
+        );
+    }
+
+    #[test]
+    fn localized_entries() {
+        let oca = OcaBuilder::new()
+            .add_attribute("sex", "Text")
+            .add_entry("en", "sex", "F", "female")
+            .add_entry("de", "sex", "F", "weiblich")
+            .build()
+            .unwrap();
+        assert_eq!(oca.localized_entry("sex", "F", "de-CH"), "weiblich");
+        assert_eq!(oca.localized_entry("sex", "F", "fr"), "female");
+        assert_eq!(oca.localized_entry("sex", "X", "de"), "X");
+    }
 }
@@ -4,117 +4,604 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::io::{Cursor, Write};
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Write},
+};
 
 use serde_json::{Map, Value};
-use zip::{write::SimpleFileOptions, ZipWriter};
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
 
 use crate::{
+    error::OcaError,
+    integrity::IntegrityViolation,
     models::{CaptureBase, Oca, OcaLayer},
-    said::{verify_said, Said},
+    overlays::Overlays,
+    said::{said_from_str, verify_legacy_said_from_str, verify_said, verify_said_from_str, Said},
 };
 
-pub fn generate_zip(oca: Oca) -> Result<Vec<u8>, String> {
+/// Controls the archive written by [`generate_zip_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    /// Deflate the entries instead of storing them uncompressed.
+    pub deflate: bool,
+}
+
+pub fn generate_zip(oca: Oca) -> Result<Vec<u8>, OcaError> {
+    generate_zip_with_options(oca, &GenerateOptions::default())
+}
+
+/// Writes `oca` as a zip bundle.
+///
+/// Dependencies are written next to the root, each capture base listing its
+/// overlays under its digest in `meta.json`. The output only depends on `oca`
+/// and `options`: entries carry a fixed timestamp and permissions, and follow
+/// `meta.json` sorted by name.
+pub fn generate_zip_with_options(oca: Oca, options: &GenerateOptions) -> Result<Vec<u8>, OcaError> {
     let mut meta_map = Map::new();
     meta_map.insert(
         "root".to_string(),
         Value::String(oca.capture_base.digest.clone()),
     );
     let mut root_files = Map::new();
-    let mut overlays = Map::new();
-    let mut overlay_files = vec![];
-    for (name, mut overlay) in oca.overlays {
-        if overlay.update_digest().is_err() {
-            return Err("Failed updating said".to_string());
+    let mut files = vec![];
+    let mut dependencies = oca.dependencies;
+    dependencies.remove(&oca.capture_base.digest);
+    let bundles = std::iter::once((oca.capture_base, oca.overlays)).chain(
+        dependencies
+            .into_values()
+            .map(|dependency| (dependency.capture_base, dependency.overlays)),
+    );
+    for (capture_base, bundle_overlays) in bundles {
+        let mut overlays = Map::new();
+        for (name, overlay) in bundle_overlays.iter() {
+            let document =
+                layer_document(overlay.clone(), bundle_overlays.source(overlay.digest()))?;
+            let digest = document_digest(&document);
+            overlays.insert(name.clone(), Value::String(digest.clone()));
+            files.push((format!("{digest}.json"), document.to_string().into_bytes()));
         }
-        let digest = overlay.digest().to_string();
-        overlays.insert(name, Value::String(digest.clone()));
-        overlay_files.push((
-            format!("{digest}.json"),
-            serde_json::to_string(&overlay)
-                .map_err(|e| format!("{e}"))?
-                .as_bytes()
-                .to_vec(),
+        root_files.insert(capture_base.digest.clone(), Value::Object(overlays));
+        files.push((
+            format!("{}.json", capture_base.digest),
+            capture_base_document(&capture_base)?
+                .to_string()
+                .into_bytes(),
         ));
     }
-    root_files.insert(oca.capture_base.digest.clone(), Value::Object(overlays));
     meta_map.insert("files".to_string(), Value::Object(root_files));
-    let meta_file = serde_json::to_string(&Value::Object(meta_map)).map_err(|e| format!("{e}"))?;
+    let meta_file =
+        serde_json::to_string(&Value::Object(meta_map)).map_err(OcaError::Serialization)?;
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    // identical overlays share a digest and therefore a file
+    files.dedup_by(|(a, _), (b, _)| a == b);
+
     let mut archive_buffer = vec![];
     {
         let mut zip_archive = ZipWriter::new(Cursor::new(&mut archive_buffer));
-        let options =
-            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
-        zip_archive
-            .start_file("meta.json", options)
-            .map_err(|e| format!("{e}"))?;
-        zip_archive
-            .write_all(meta_file.as_bytes())
-            .map_err(|e| format!("{e}"))?;
-
-        zip_archive
-            .start_file(format!("{}.json", oca.capture_base.digest), options)
-            .map_err(|e| format!("{e}"))?;
-        zip_archive
-            .write_all(
-                serde_json::to_string(&oca.capture_base)
-                    .map_err(|e| format!("{e}"))?
-                    .as_bytes(),
-            )
-            .map_err(|e| format!("{e}"))?;
-        for (name, file) in overlay_files {
-            zip_archive
-                .start_file(name, options)
-                .map_err(|e| format!("{e}"))?;
-            zip_archive.write_all(&file).map_err(|e| format!("{e}"))?;
+        let compression = if options.deflate {
+            CompressionMethod::Deflated
+        } else {
+            CompressionMethod::Stored
+        };
+        let file_options = SimpleFileOptions::default()
+            .compression_method(compression)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(0o644);
+        zip_archive.start_file("meta.json", file_options)?;
+        zip_archive.write_all(meta_file.as_bytes())?;
+        for (name, file) in files {
+            zip_archive.start_file(name, file_options)?;
+            zip_archive.write_all(&file)?;
         }
+        zip_archive.finish()?;
     }
 
     Ok(archive_buffer)
 }
 
-pub fn parse_zip(file: &[u8]) -> Result<Oca, String> {
-    let mut archive = zip::ZipArchive::new(Cursor::new(file)).unwrap();
-    let meta = archive.by_name("meta.json").unwrap();
-    let meta: serde_json::Value = serde_json::from_reader(meta).unwrap();
-    let root = meta.get("root").unwrap().as_str().unwrap();
-    let capture_base = archive.by_name(&format!("{root}.json")).unwrap();
-    assert!(verify_said(root, capture_base).unwrap());
-    let capture_base = archive.by_name(&format!("{root}.json")).unwrap();
-    let capture_base: CaptureBase =
-        serde_json::from_reader(capture_base).map_err(|e| format!("{e}"))?;
-    let mut overlays = vec![];
-    for (key, value) in meta
+/// Controls how forgiving [`parse_zip_with_options`] is with inconsistent bundles.
+///
+/// Layers whose SAID does not match their content are always rejected, lenient
+/// mode only tolerates bundles that are incomplete or untidy.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Reject the bundle on the first inconsistency instead of collecting a warning.
+    pub strict: bool,
+    /// Run [`Oca::verify_integrity`] on the parsed bundle.
+    pub verify_integrity: bool,
+    /// Also accept the non spec compliant Blake3 SAIDs written by earlier
+    /// versions of this crate.
+    pub accept_legacy_saids: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            verify_integrity: true,
+            accept_legacy_saids: false,
+        }
+    }
+}
+
+/// An inconsistency found while parsing a bundle in lenient mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    /// The overlay listed under `name` in `meta.json` was not loaded.
+    SkippedOverlay { name: String, reason: SkipReason },
+    /// A file in the archive is not referenced by `meta.json`.
+    UnreferencedFile(String),
+    /// More than one overlay of the same type (and language) is present.
+    DuplicateOverlay {
+        name: String,
+        overlay_type: String,
+        language: Option<String>,
+    },
+    /// The overlay's `capture_base` does not match the root of the bundle.
+    CaptureBaseMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    /// Any other violation found by [`Oca::verify_integrity`].
+    Integrity(IntegrityViolation),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    /// The `meta.json` entry is not a string, or the overlay of a JSON bundle
+    /// has no digest.
+    InvalidReference,
+    MissingFile(String),
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseWarning::SkippedOverlay { name, reason } => match reason {
+                SkipReason::InvalidReference => {
+                    write!(f, "overlay {name} skipped: invalid reference")
+                }
+                SkipReason::MissingFile(file) => {
+                    write!(f, "overlay {name} skipped: {file} is missing")
+                }
+            },
+            ParseWarning::UnreferencedFile(file) => {
+                write!(f, "{file} is not referenced by meta.json")
+            }
+            ParseWarning::DuplicateOverlay {
+                name,
+                overlay_type,
+                language,
+            } => match language {
+                Some(language) => {
+                    write!(f, "overlay {name} duplicates {overlay_type} ({language})")
+                }
+                None => write!(f, "overlay {name} duplicates {overlay_type}"),
+            },
+            ParseWarning::CaptureBaseMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "overlay {name} belongs to capture base {actual} instead of {expected}"
+            ),
+            ParseWarning::Integrity(violation) => violation.fmt(f),
+        }
+    }
+}
+
+impl From<IntegrityViolation> for ParseWarning {
+    fn from(violation: IntegrityViolation) -> Self {
+        match violation {
+            IntegrityViolation::CaptureBaseMismatch {
+                overlay,
+                expected,
+                actual,
+            } => ParseWarning::CaptureBaseMismatch {
+                name: overlay,
+                expected,
+                actual,
+            },
+            IntegrityViolation::DuplicateOverlay {
+                overlay,
+                overlay_type,
+                language,
+            } => ParseWarning::DuplicateOverlay {
+                name: overlay,
+                overlay_type,
+                language,
+            },
+            violation => ParseWarning::Integrity(violation),
+        }
+    }
+}
+
+impl ParseWarning {
+    fn into_error(self) -> OcaError {
+        match self {
+            ParseWarning::SkippedOverlay {
+                reason: SkipReason::MissingFile(file),
+                ..
+            } => OcaError::MissingLayerFile(file),
+            warning => OcaError::Inconsistent(warning),
+        }
+    }
+}
+
+pub fn parse_zip(file: &[u8]) -> Result<Oca, OcaError> {
+    parse_zip_with_options(file, &ParseOptions::default()).map(|(oca, _)| oca)
+}
+
+/// Parses a bundle, returning the warnings collected in lenient mode.
+///
+/// In strict mode the first inconsistency is returned as an error instead.
+pub fn parse_zip_with_options(
+    file: &[u8],
+    options: &ParseOptions,
+) -> Result<(Oca, Vec<ParseWarning>), OcaError> {
+    let mut warnings = vec![];
+    let mut warn = |warning: ParseWarning| {
+        if options.strict {
+            return Err(warning.into_error());
+        }
+        warnings.push(warning);
+        Ok(())
+    };
+    let mut archive = ZipArchive::new(Cursor::new(file))?;
+    let meta = archive
+        .by_name("meta.json")
+        .map_err(|_| OcaError::MissingMeta)?;
+    let meta: Value = serde_json::from_reader(meta).map_err(|source| OcaError::Json {
+        file: "meta.json".to_string(),
+        source,
+    })?;
+    let root = meta
+        .get("root")
+        .and_then(Value::as_str)
+        .ok_or(OcaError::MissingRoot)?;
+    let files = meta
         .get("files")
-        .unwrap()
-        .get(root)
-        .unwrap()
-        .as_object()
-        .unwrap()
+        .and_then(Value::as_object)
+        .ok_or_else(|| OcaError::InvalidMeta("no file list".to_string()))?;
+    let mut referenced = vec!["meta.json".to_string()];
+    let mut oca = read_zip_bundle(
+        &mut archive,
+        root,
+        files,
+        &mut referenced,
+        options,
+        &mut warn,
+    )?;
+    // every other capture base in meta.json is a dependency of the root
+    for digest in files.keys().filter(|digest| *digest != root) {
+        let dependency = read_zip_bundle(
+            &mut archive,
+            digest,
+            files,
+            &mut referenced,
+            options,
+            &mut warn,
+        )?;
+        oca.dependencies.insert(digest.clone(), dependency);
+    }
+    let unreferenced = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/') && !referenced.iter().any(|r| r == name))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for name in unreferenced {
+        warn(ParseWarning::UnreferencedFile(name))?;
+    }
+    check_integrity(&oca, options, &mut warnings)?;
+    Ok((oca, warnings))
+}
+
+/// Runs [`Oca::verify_integrity`] if enabled, failing in strict mode and adding
+/// the violations to `warnings` otherwise.
+fn check_integrity(
+    oca: &Oca,
+    options: &ParseOptions,
+    warnings: &mut Vec<ParseWarning>,
+) -> Result<(), OcaError> {
+    if !options.verify_integrity {
+        return Ok(());
+    }
+    if options.strict {
+        return oca.verify_integrity();
+    }
+    for warning in oca
+        .integrity_violations()
+        .into_iter()
+        .map(ParseWarning::from)
     {
+        // overlays of other capture bases are already reported while parsing
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the capture base `root` and the overlays `files` in `meta.json` lists
+/// for it, noting the files read in `referenced`.
+fn read_zip_bundle(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    root: &str,
+    files: &Map<String, Value>,
+    referenced: &mut Vec<String>,
+    options: &ParseOptions,
+    warn: &mut impl FnMut(ParseWarning) -> Result<(), OcaError>,
+) -> Result<Oca, OcaError> {
+    let capture_base_name = format!("{root}.json");
+    verify_said(
+        root,
+        by_name(archive, &capture_base_name)?,
+        options.accept_legacy_saids,
+    )?;
+    let source: Value = read_json(archive, &capture_base_name)?;
+    let mut capture_base: CaptureBase = from_source(&source, &capture_base_name)?;
+    capture_base.source = Some(source);
+    referenced.push(capture_base_name);
+    let layers = files
+        .get(root)
+        .and_then(Value::as_object)
+        .ok_or_else(|| OcaError::InvalidMeta(format!("no file list for root {root}")))?;
+    let mut overlays = Overlays::new();
+    for (key, value) in layers {
+        let skip = |reason| ParseWarning::SkippedOverlay {
+            name: key.to_string(),
+            reason,
+        };
         let Some(value) = value.as_str() else {
-            println!("value not str");
+            warn(skip(SkipReason::InvalidReference))?;
             continue;
         };
-        let Ok(layer_zip_file) = archive.by_name(&format!("{value}.json")) else {
-            println!("Archive not found {key}");
+        let layer_name = format!("{value}.json");
+        referenced.push(layer_name.clone());
+        let Ok(layer_zip_file) = archive.by_name(&layer_name) else {
+            warn(skip(SkipReason::MissingFile(layer_name)))?;
             continue;
         };
-        if !verify_said(value, layer_zip_file).unwrap_or(false) {
-            let var_name = Err("SAID failed".to_string());
-            return var_name;
+        verify_said(value, layer_zip_file, options.accept_legacy_saids)?;
+        let source: Value = read_json(archive, &layer_name)?;
+        let layer: OcaLayer = from_source(&source, &layer_name)?;
+        push_overlay(&mut overlays, key, layer, source, root, warn)?;
+    }
+    Ok(Oca {
+        capture_base,
+        overlays,
+        dependencies: BTreeMap::new(),
+    })
+}
+
+/// Parses a bundle in the single document JSON representation written by
+/// [`generate_json_bundle`].
+pub fn parse_json_bundle(file: &[u8]) -> Result<Oca, OcaError> {
+    parse_json_bundle_with_options(file, &ParseOptions::default()).map(|(oca, _)| oca)
+}
+
+/// Parses a JSON bundle with the same SAID verification and warnings as
+/// [`parse_zip_with_options`].
+pub fn parse_json_bundle_with_options(
+    file: &[u8],
+    options: &ParseOptions,
+) -> Result<(Oca, Vec<ParseWarning>), OcaError> {
+    let mut warnings = vec![];
+    let mut warn = |warning: ParseWarning| {
+        if options.strict {
+            return Err(warning.into_error());
         }
-        let Ok(layer_zip_file) = archive.by_name(&format!("{value}.json")) else {
-            println!("Archive not found {key}");
+        warnings.push(warning);
+        Ok(())
+    };
+    let bundle: Value = serde_json::from_slice(file).map_err(|source| OcaError::Json {
+        file: "bundle".to_string(),
+        source,
+    })?;
+    let mut oca = read_json_bundle(&bundle, options, &mut warn)?;
+    // dependencies may list their own dependencies in turn
+    let mut pending = match bundle.get("dependencies") {
+        None => vec![],
+        Some(Value::Array(dependencies)) => dependencies.iter().collect(),
+        Some(_) => {
+            return Err(OcaError::InvalidBundle(
+                "dependencies is no list".to_string(),
+            ))
+        }
+    };
+    while let Some(dependency) = pending.pop() {
+        oca.add_dependency(read_json_bundle(dependency, options, &mut warn)?);
+        if let Some(Value::Array(nested)) = dependency.get("dependencies") {
+            pending.extend(nested);
+        }
+    }
+    check_integrity(&oca, options, &mut warnings)?;
+    Ok((oca, warnings))
+}
+
+fn read_json_bundle(
+    bundle: &Value,
+    options: &ParseOptions,
+    warn: &mut impl FnMut(ParseWarning) -> Result<(), OcaError>,
+) -> Result<Oca, OcaError> {
+    let capture_base = bundle
+        .get("capture_base")
+        .ok_or_else(|| OcaError::InvalidBundle("no capture_base".to_string()))?;
+    let root = capture_base
+        .get("digest")
+        .and_then(Value::as_str)
+        .ok_or(OcaError::MissingRoot)?;
+    verify_said_value(root, capture_base, options)?;
+    let source = capture_base;
+    let mut capture_base: CaptureBase = from_source(source, "capture_base")?;
+    capture_base.source = Some(source.clone());
+    let layers = bundle
+        .get("overlays")
+        .and_then(Value::as_object)
+        .ok_or_else(|| OcaError::InvalidBundle("no overlays object".to_string()))?;
+    let mut overlays = Overlays::new();
+    for (key, value) in layers {
+        let skip = |reason| ParseWarning::SkippedOverlay {
+            name: key.to_string(),
+            reason,
+        };
+        let Some(said) = value.get("digest").and_then(Value::as_str) else {
+            warn(skip(SkipReason::InvalidReference))?;
             continue;
         };
-
-        let layer: OcaLayer =
-            serde_json::from_reader(layer_zip_file).map_err(|e| format!("{e}"))?;
-        overlays.push((key.to_string(), layer));
+        verify_said_value(said, value, options)?;
+        let layer: OcaLayer = from_source(value, key)?;
+        push_overlay(&mut overlays, key, layer, value.clone(), root, warn)?;
     }
     Ok(Oca {
         capture_base,
         overlays,
+        dependencies: BTreeMap::new(),
+    })
+}
+
+/// Writes `oca` as a single JSON document,
+/// `{"capture_base": {..}, "overlays": {"<name>": {..}, ..}}`, with a
+/// `dependencies` list of bundles of the same shape if there are any.
+pub fn generate_json_bundle(oca: Oca) -> Result<Vec<u8>, OcaError> {
+    let mut bundle = json_bundle(oca.capture_base, oca.overlays)?;
+    let dependencies = oca
+        .dependencies
+        .into_values()
+        .map(|dependency| {
+            json_bundle(dependency.capture_base, dependency.overlays).map(Value::Object)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if !dependencies.is_empty() {
+        bundle.insert("dependencies".to_string(), Value::Array(dependencies));
+    }
+    serde_json::to_vec_pretty(&bundle).map_err(OcaError::Serialization)
+}
+
+fn json_bundle(
+    capture_base: CaptureBase,
+    bundle_overlays: Overlays,
+) -> Result<Map<String, Value>, OcaError> {
+    let mut overlays = Map::new();
+    for (name, overlay) in bundle_overlays.iter() {
+        let document = layer_document(overlay.clone(), bundle_overlays.source(overlay.digest()))?;
+        overlays.insert(name.clone(), document);
+    }
+    let mut bundle = Map::new();
+    bundle.insert(
+        "capture_base".to_string(),
+        capture_base_document(&capture_base)?,
+    );
+    bundle.insert("overlays".to_string(), Value::Object(overlays));
+    Ok(bundle)
+}
+
+/// Parses either a zip or a JSON bundle, telling them apart by their first bytes.
+pub fn parse_bundle(file: &[u8]) -> Result<Oca, OcaError> {
+    parse_bundle_with_options(file, &ParseOptions::default()).map(|(oca, _)| oca)
+}
+
+pub fn parse_bundle_with_options(
+    file: &[u8],
+    options: &ParseOptions,
+) -> Result<(Oca, Vec<ParseWarning>), OcaError> {
+    let without_bom = file.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(file);
+    if without_bom.trim_ascii_start().starts_with(b"{") {
+        parse_json_bundle_with_options(without_bom, options)
+    } else {
+        parse_zip_with_options(file, options)
+    }
+}
+
+/// Adds a parsed overlay, warning if it belongs to another capture base.
+/// Duplicates are reported by [`check_integrity`].
+fn push_overlay(
+    overlays: &mut Overlays,
+    key: &str,
+    layer: OcaLayer,
+    source: Value,
+    root: &str,
+    warn: &mut impl FnMut(ParseWarning) -> Result<(), OcaError>,
+) -> Result<(), OcaError> {
+    if layer.capture_base() != root {
+        warn(ParseWarning::CaptureBaseMismatch {
+            name: key.to_string(),
+            expected: root.to_string(),
+            actual: layer.capture_base().to_string(),
+        })?;
+    }
+    overlays.push_parsed(key.to_string(), layer, source);
+    Ok(())
+}
+
+/// The document to write for `layer`: the one it was parsed from while the
+/// model still matches it, so a bundle keeps its SAIDs whatever the field
+/// order of its files, or else the model with a recalculated digest.
+fn layer_document(mut layer: OcaLayer, source: Option<&Value>) -> Result<Value, OcaError> {
+    let document = serde_json::to_value(&layer).map_err(OcaError::Serialization)?;
+    // maps compare regardless of key order
+    if let Some(source) = source.filter(|source| **source == document) {
+        return Ok(source.clone());
+    }
+    layer.update_digest()?;
+    serde_json::to_value(&layer).map_err(OcaError::Serialization)
+}
+
+fn capture_base_document(capture_base: &CaptureBase) -> Result<Value, OcaError> {
+    let document = serde_json::to_value(capture_base).map_err(OcaError::Serialization)?;
+    match &capture_base.source {
+        Some(source) if *source == document => Ok(source.clone()),
+        _ => Ok(document),
+    }
+}
+
+fn document_digest(document: &Value) -> String {
+    document
+        .get("digest")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn from_source<T: serde::de::DeserializeOwned>(source: &Value, file: &str) -> Result<T, OcaError> {
+    serde_json::from_value(source.clone()).map_err(|source| OcaError::Json {
+        file: file.to_string(),
+        source,
+    })
+}
+
+fn verify_said_value(said: &str, value: &Value, options: &ParseOptions) -> Result<(), OcaError> {
+    let json_representation = value.to_string();
+    let matches = verify_said_from_str(said, &json_representation)?
+        || (options.accept_legacy_saids && verify_legacy_said_from_str(said, &json_representation));
+    if !matches {
+        return Err(OcaError::SaidMismatch {
+            expected: said.to_string(),
+            actual: said_from_str(said, &json_representation),
+        });
+    }
+    Ok(())
+}
+
+fn by_name<'a>(
+    archive: &'a mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<zip::read::ZipFile<'a>, OcaError> {
+    archive
+        .by_name(name)
+        .map_err(|_| OcaError::MissingLayerFile(name.to_string()))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<T, OcaError> {
+    let file = by_name(archive, name)?;
+    serde_json::from_reader(file).map_err(|source| OcaError::Json {
+        file: name.to_string(),
+        source,
     })
 }
@@ -0,0 +1,825 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reading and writing OCAfiles, the text form of an OCA definition.
+//!
+//! ```text
+//! -- comments start with two dashes
+//! ADD CLASSIFICATION GICS:45102010
+//! ADD ATTRIBUTE givenName=Text dateOfBirth=DateTime
+//! ADD FLAGGED_ATTRIBUTES dateOfBirth
+//! ADD META en PROPS name="Identity" description="A person"
+//! ADD LABEL en ATTRS givenName="Given name" \
+//!     dateOfBirth="Date of birth"
+//! ADD FORMAT ATTRS dateOfBirth="%Y%m%d"
+//! ADD ENTRY_CODE ATTRS sex=["F","M"]
+//! ADD ENTRY en ATTRS sex={"F":"female","M":"male"}
+//! ```
+//!
+//! Every statement is one line, a trailing `\` continues it on the next one.
+//! Statements for the same overlay are merged. Besides the statements above
+//! there are `INFORMATION`, `CHARACTER_ENCODING`, `CONFORMANCE`, `UNIT`,
+//! `CARDINALITY`, `STANDARD`, `SENSITIVE`, `SUBSET`, `ATTRIBUTE_MAPPING` and
+//! `STYLE`, which takes the style JSON as its argument.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    builder::OcaBuilder,
+    error::OcaError,
+    models::{Oca, OcaLayer, StyleJson},
+};
+
+/// A syntax or reference error in an OCAfile, positioned by 1-based line and column.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{line}:{column}: {message}")]
+pub struct OcaFileError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Parses an OCAfile and computes the digests of the resulting bundle.
+pub fn parse_ocafile(source: &str) -> Result<Oca, OcaError> {
+    parse_ocafile_with_dependencies(source, vec![])
+}
+
+/// Parses an OCAfile whose `refs:` attributes refer to the capture bases of
+/// `dependencies`.
+pub fn parse_ocafile_with_dependencies(
+    source: &str,
+    dependencies: Vec<Oca>,
+) -> Result<Oca, OcaError> {
+    let mut parser = Parser::new(source);
+    let mut definition = Definition::default();
+    loop {
+        parser.skip_blanks();
+        match parser.peek() {
+            None => break,
+            Some('\n') => {
+                parser.bump();
+                continue;
+            }
+            Some(_) => {}
+        }
+        parser.statement(&mut definition)?;
+        parser.skip_blanks();
+        if !parser.at_end_of_statement() {
+            return Err(parser.error("expected end of statement").into());
+        }
+    }
+    for (attribute, (line, column)) in &definition.references {
+        if !definition.attributes.contains(attribute) {
+            return Err(OcaFileError {
+                line: *line,
+                column: *column,
+                message: format!("unknown attribute {attribute}"),
+            }
+            .into());
+        }
+    }
+    dependencies
+        .into_iter()
+        .fold(definition.builder, OcaBuilder::add_dependency)
+        .build()
+}
+
+/// Prints `oca` as an OCAfile, one statement per overlay in bundle order.
+///
+/// Label categories, fields not covered by the models and overlays of unknown
+/// types have no OCAfile representation and are left out.
+pub fn to_ocafile(oca: &Oca) -> String {
+    let mut out = String::new();
+    let capture_base = oca.capture_base();
+    if let Some(classification) = capture_base.classification() {
+        statement(
+            &mut out,
+            "ADD CLASSIFICATION",
+            &[],
+            vec![],
+            &[text(classification)],
+        );
+    }
+    let attributes = capture_base
+        .attributes()
+        .iter()
+        .map(|(name, attribute_type)| (name.as_str(), text(&attribute_type.to_string())))
+        .collect();
+    if !capture_base.attributes().is_empty() {
+        statement(&mut out, "ADD ATTRIBUTE", &[], attributes, &[]);
+    }
+    if !capture_base.flagged_attributes().is_empty() {
+        let flagged = words(capture_base.flagged_attributes());
+        statement(&mut out, "ADD FLAGGED_ATTRIBUTES", &[], vec![], &flagged);
+    }
+    for (_, layer) in oca.overlays() {
+        match layer {
+            OcaLayer::Meta(meta) => {
+                let props = vec![
+                    ("name", text(meta.name())),
+                    ("description", text(meta.description())),
+                ];
+                statement(
+                    &mut out,
+                    "ADD META",
+                    &[meta.language(), "PROPS"],
+                    props,
+                    &[],
+                );
+            }
+            OcaLayer::Label(label) => {
+                let attrs = texts(label.attribute_labels());
+                statement(
+                    &mut out,
+                    "ADD LABEL",
+                    &[label.language(), "ATTRS"],
+                    attrs,
+                    &[],
+                );
+            }
+            OcaLayer::Information(information) => {
+                let attrs = texts(information.attribute_information());
+                let head = [information.language(), "ATTRS"];
+                statement(&mut out, "ADD INFORMATION", &head, attrs, &[]);
+            }
+            OcaLayer::CharacterEncoding(character_encoding) => {
+                let attrs = character_encoding
+                    .attribute_character_encoding()
+                    .iter()
+                    .map(|(name, encoding)| (name.as_str(), enum_text(encoding)))
+                    .collect();
+                statement(&mut out, "ADD CHARACTER_ENCODING", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::Format(format) => {
+                let attrs = texts(format.attribute_formats());
+                statement(&mut out, "ADD FORMAT", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::Conformance(conformance) => {
+                let attrs = conformance
+                    .attribute_conformance()
+                    .iter()
+                    .map(|(name, policy)| (name.as_str(), enum_text(policy)))
+                    .collect();
+                statement(&mut out, "ADD CONFORMANCE", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::Unit(unit) => {
+                let attrs = texts(unit.attribute_units());
+                let head = [unit.metric_system(), "ATTRS"];
+                statement(&mut out, "ADD UNIT", &head, attrs, &[]);
+            }
+            OcaLayer::Cardinality(cardinality) => {
+                let attrs = texts(cardinality.attribute_cardinality());
+                statement(&mut out, "ADD CARDINALITY", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::Standard(standard) => {
+                let attrs = texts(standard.attribute_standards());
+                statement(&mut out, "ADD STANDARD", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::EntryCode(entry_code) => {
+                let attrs = entry_code
+                    .attribute_entry_codes()
+                    .iter()
+                    .map(|(name, codes)| (name.as_str(), json(codes)))
+                    .collect();
+                statement(&mut out, "ADD ENTRY_CODE", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::Entry(entry) => {
+                let attrs = entry
+                    .attribute_entries()
+                    .iter()
+                    .map(|(name, entries)| (name.as_str(), json(entries)))
+                    .collect();
+                statement(
+                    &mut out,
+                    "ADD ENTRY",
+                    &[entry.language(), "ATTRS"],
+                    attrs,
+                    &[],
+                );
+            }
+            OcaLayer::Sensitive(sensitive) => {
+                let attrs = words(sensitive.attributes());
+                statement(&mut out, "ADD SENSITIVE", &["ATTRS"], vec![], &attrs);
+            }
+            OcaLayer::Subset(subset) => {
+                let attrs = words(subset.attributes());
+                statement(&mut out, "ADD SUBSET", &["ATTRS"], vec![], &attrs);
+            }
+            OcaLayer::AttributeMapping(attribute_mapping) => {
+                let attrs = texts(attribute_mapping.attribute_mapping());
+                statement(&mut out, "ADD ATTRIBUTE_MAPPING", &["ATTRS"], attrs, &[]);
+            }
+            OcaLayer::Style(style) => {
+                statement(
+                    &mut out,
+                    "ADD STYLE",
+                    &[],
+                    vec![],
+                    &[json(style.style_json())],
+                );
+            }
+            OcaLayer::Other(_) => {}
+        }
+    }
+    out
+}
+
+fn statement(
+    out: &mut String,
+    command: &str,
+    head: &[&str],
+    pairs: Vec<(&str, String)>,
+    values: &[String],
+) {
+    out.push_str(command);
+    for word in head {
+        out.push(' ');
+        out.push_str(&text(word));
+    }
+    for value in values {
+        out.push(' ');
+        out.push_str(value);
+    }
+    // one attribute per line keeps diffs of larger overlays readable
+    let separator = if pairs.len() > 1 { " \\\n    " } else { " " };
+    for (key, value) in pairs {
+        let _ = write!(out, "{separator}{}={value}", key_text(key));
+    }
+    out.push('\n');
+}
+
+fn texts(map: &BTreeMap<String, String>) -> Vec<(&str, String)> {
+    map.iter()
+        .map(|(key, value)| (key.as_str(), text(value)))
+        .collect()
+}
+
+fn words(attributes: &[String]) -> Vec<String> {
+    attributes.iter().map(|a| key_text(a)).collect()
+}
+
+fn json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn enum_text<T: serde::Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(value)) => text(&value),
+        _ => json(value),
+    }
+}
+
+/// Prints `value` bare if it reads back as a single word, quoted otherwise.
+fn text(value: &str) -> String {
+    let bare = !value.is_empty()
+        && !value.starts_with(['"', '[', '{'])
+        && !value.starts_with("--")
+        && !value.contains(char::is_whitespace)
+        && !value.ends_with('\\');
+    if bare {
+        value.to_string()
+    } else {
+        json(&value)
+    }
+}
+
+fn key_text(key: &str) -> String {
+    if key.contains('=') {
+        json(&key)
+    } else {
+        text(key)
+    }
+}
+
+type Position = (usize, usize);
+
+#[derive(Debug, Default)]
+struct Definition {
+    builder: OcaBuilder,
+    attributes: BTreeSet<String>,
+    references: Vec<(String, Position)>,
+    meta: BTreeMap<String, (String, String)>,
+}
+
+impl Definition {
+    fn update(&mut self, f: impl FnOnce(OcaBuilder) -> OcaBuilder) {
+        self.builder = f(std::mem::take(&mut self.builder));
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        Self {
+            chars: source.chars().collect(),
+            index: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+    fn position(&self) -> Position {
+        (self.line, self.column)
+    }
+    fn error(&self, message: &str) -> OcaFileError {
+        error_at(self.position(), message.to_string())
+    }
+
+    /// Skips spaces, comments and line continuations, stopping at a line break.
+    fn skip_blanks(&mut self) {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(' ' | '\t' | '\r'), _) => {
+                    self.bump();
+                }
+                (Some('\\'), Some('\n')) => {
+                    self.bump();
+                    self.bump();
+                }
+                (Some('\\'), Some('\r')) if self.peek_at(2) == Some('\n') => {
+                    self.bump();
+                    self.bump();
+                    self.bump();
+                }
+                (Some('-'), Some('-')) => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+    fn at_end_of_statement(&self) -> bool {
+        matches!(self.peek(), None | Some('\n'))
+    }
+
+    fn word_until(&mut self, stop: &[char]) -> Result<String, OcaFileError> {
+        self.skip_blanks();
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || stop.contains(&c) || (c == '\\' && self.is_continuation()) {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        if word.is_empty() {
+            return Err(self.error("expected a word"));
+        }
+        Ok(word)
+    }
+    fn is_continuation(&self) -> bool {
+        match self.peek_at(1) {
+            Some('\n') => true,
+            Some('\r') => self.peek_at(2) == Some('\n'),
+            _ => false,
+        }
+    }
+    fn keyword(&mut self, expected: &str) -> Result<(), OcaFileError> {
+        self.skip_blanks();
+        let position = self.position();
+        let word = self.word_until(&[])?;
+        if !word.eq_ignore_ascii_case(expected) {
+            return Err(error_at(
+                position,
+                format!("expected {expected}, found {word}"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// A quoted string or a bare word.
+    fn text(&mut self) -> Result<String, OcaFileError> {
+        self.skip_blanks();
+        match self.peek() {
+            Some('"') => {
+                let position = self.position();
+                let raw = self.quoted()?;
+                parse_json(&raw, position)
+            }
+            Some('[' | '{') => Err(self.error("expected text, found JSON")),
+            _ => self.word_until(&[]),
+        }
+    }
+    fn key(&mut self) -> Result<(String, Position), OcaFileError> {
+        self.skip_blanks();
+        let position = self.position();
+        let key = if self.peek() == Some('"') {
+            let raw = self.quoted()?;
+            parse_json(&raw, position)?
+        } else {
+            self.word_until(&['='])?
+        };
+        Ok((key, position))
+    }
+    fn quoted(&mut self) -> Result<String, OcaFileError> {
+        let start = self.position();
+        let mut raw = String::new();
+        raw.extend(self.bump());
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(error_at(start, "unterminated string".into())),
+                Some('\\') => {
+                    raw.push('\\');
+                    raw.extend(self.bump());
+                }
+                Some('"') => {
+                    raw.push('"');
+                    return Ok(raw);
+                }
+                Some(c) => raw.push(c),
+            }
+        }
+    }
+    /// A JSON array or object, scanned up to its matching bracket.
+    fn json<T: DeserializeOwned>(&mut self) -> Result<T, OcaFileError> {
+        self.skip_blanks();
+        let position = self.position();
+        if !matches!(self.peek(), Some('[' | '{')) {
+            return Err(self.error("expected a JSON array or object"));
+        }
+        let mut raw = String::new();
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                None => return Err(error_at(position, "unterminated JSON value".into())),
+                Some('"') => raw.push_str(&self.quoted()?),
+                Some(c) => {
+                    self.bump();
+                    raw.push(c);
+                    match c {
+                        '[' | '{' => depth += 1,
+                        ']' | '}' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        parse_json(&raw, position)
+    }
+
+    /// `key=value` pairs up to the end of the statement, at least one.
+    fn pairs<T>(
+        &mut self,
+        mut value: impl FnMut(&mut Self) -> Result<T, OcaFileError>,
+    ) -> Result<Vec<(String, Position, T)>, OcaFileError> {
+        let mut pairs = vec![];
+        loop {
+            self.skip_blanks();
+            if self.at_end_of_statement() {
+                break;
+            }
+            let (key, position) = self.key()?;
+            if self.peek() != Some('=') {
+                return Err(self.error("expected ="));
+            }
+            self.bump();
+            pairs.push((key, position, value(self)?));
+        }
+        if pairs.is_empty() {
+            return Err(self.error("expected attribute=value"));
+        }
+        Ok(pairs)
+    }
+    /// Attribute names up to the end of the statement, at least one.
+    fn names(&mut self) -> Result<Vec<(String, Position)>, OcaFileError> {
+        let mut names = vec![];
+        loop {
+            self.skip_blanks();
+            if self.at_end_of_statement() {
+                break;
+            }
+            names.push(self.key()?);
+        }
+        if names.is_empty() {
+            return Err(self.error("expected an attribute name"));
+        }
+        Ok(names)
+    }
+
+    fn attribute_pairs<T>(
+        &mut self,
+        definition: &mut Definition,
+        value: impl FnMut(&mut Self) -> Result<T, OcaFileError>,
+    ) -> Result<Vec<(String, T)>, OcaFileError> {
+        self.keyword("ATTRS")?;
+        let pairs = self.pairs(value)?;
+        Ok(pairs
+            .into_iter()
+            .map(|(key, position, value)| {
+                definition.references.push((key.clone(), position));
+                (key, value)
+            })
+            .collect())
+    }
+    fn attribute_names(
+        &mut self,
+        definition: &mut Definition,
+    ) -> Result<Vec<String>, OcaFileError> {
+        Ok(self
+            .names()?
+            .into_iter()
+            .map(|(name, position)| {
+                definition.references.push((name.clone(), position));
+                name
+            })
+            .collect())
+    }
+
+    fn statement(&mut self, definition: &mut Definition) -> Result<(), OcaFileError> {
+        self.keyword("ADD")?;
+        self.skip_blanks();
+        let position = self.position();
+        let object = self.word_until(&[])?.to_ascii_uppercase();
+        match object.as_str() {
+            "CLASSIFICATION" => {
+                let classification = self.text()?;
+                definition.update(|b| b.classification(&classification));
+            }
+            "ATTRIBUTE" => {
+                for (name, _, attribute_type) in self.pairs(Self::text)? {
+                    definition.update(|b| b.add_attribute(&name, attribute_type.as_str()));
+                    definition.attributes.insert(name);
+                }
+            }
+            "FLAGGED_ATTRIBUTES" => {
+                for name in self.attribute_names(definition)? {
+                    definition.update(|b| b.flag_attribute(&name));
+                }
+            }
+            "META" => {
+                let language = self.text()?;
+                self.keyword("PROPS")?;
+                let meta = definition.meta.entry(language.clone()).or_default();
+                for (key, position, value) in self.pairs(Self::text)? {
+                    match key.as_str() {
+                        "name" => meta.0 = value,
+                        "description" => meta.1 = value,
+                        _ => return Err(error_at(position, format!("unknown property {key}"))),
+                    }
+                }
+                let (name, description) = meta.clone();
+                definition.update(|b| b.add_meta(&language, &name, &description));
+            }
+            "LABEL" => {
+                let language = self.text()?;
+                for (attribute, label) in self.attribute_pairs(definition, Self::text)? {
+                    definition.update(|b| b.add_label(&language, &attribute, &label));
+                }
+            }
+            "INFORMATION" => {
+                let language = self.text()?;
+                for (attribute, information) in self.attribute_pairs(definition, Self::text)? {
+                    definition.update(|b| b.add_information(&language, &attribute, &information));
+                }
+            }
+            "ENTRY" => {
+                let language = self.text()?;
+                let entries: Vec<(String, BTreeMap<String, String>)> =
+                    self.attribute_pairs(definition, Self::json)?;
+                for (attribute, entries) in entries {
+                    for (code, label) in entries {
+                        definition.update(|b| b.add_entry(&language, &attribute, &code, &label));
+                    }
+                }
+            }
+            "CHARACTER_ENCODING" => {
+                for (attribute, encoding) in self.attribute_pairs(definition, |p| p.enum_value())? {
+                    definition.update(|b| b.add_character_encoding(&attribute, encoding));
+                }
+            }
+            "FORMAT" => {
+                for (attribute, format) in self.attribute_pairs(definition, Self::text)? {
+                    definition.update(|b| b.add_format(&attribute, &format));
+                }
+            }
+            "CONFORMANCE" => {
+                for (attribute, policy) in self.attribute_pairs(definition, |p| p.enum_value())? {
+                    definition.update(|b| b.add_conformance(&attribute, policy));
+                }
+            }
+            "UNIT" => {
+                self.skip_blanks();
+                let checkpoint = (self.index, self.line, self.column);
+                let metric_system = self.text()?;
+                let metric_system = if metric_system.eq_ignore_ascii_case("ATTRS") {
+                    (self.index, self.line, self.column) = checkpoint;
+                    "SI".to_string()
+                } else {
+                    metric_system
+                };
+                definition.update(|b| b.metric_system(&metric_system));
+                for (attribute, unit) in self.attribute_pairs(definition, Self::text)? {
+                    definition.update(|b| b.add_unit(&attribute, &unit));
+                }
+            }
+            "CARDINALITY" => {
+                for (attribute, cardinality) in self.attribute_pairs(definition, Self::text)? {
+                    definition.update(|b| b.add_cardinality(&attribute, &cardinality));
+                }
+            }
+            "STANDARD" => {
+                for (attribute, standard) in self.attribute_pairs(definition, Self::text)? {
+                    definition.update(|b| b.add_standard(&attribute, &standard));
+                }
+            }
+            "ENTRY_CODE" => {
+                let entry_codes: Vec<(String, Vec<String>)> =
+                    self.attribute_pairs(definition, Self::json)?;
+                for (attribute, codes) in entry_codes {
+                    let codes: Vec<&str> = codes.iter().map(String::as_str).collect();
+                    definition.update(|b| b.add_entry_codes(&attribute, &codes));
+                }
+            }
+            "SENSITIVE" => {
+                self.keyword("ATTRS")?;
+                for name in self.attribute_names(definition)? {
+                    definition.update(|b| b.add_sensitive(&name));
+                }
+            }
+            "SUBSET" => {
+                self.keyword("ATTRS")?;
+                for name in self.attribute_names(definition)? {
+                    definition.update(|b| b.add_subset(&name));
+                }
+            }
+            "ATTRIBUTE_MAPPING" => {
+                self.keyword("ATTRS")?;
+                // `$` maps a whole object and is no attribute reference
+                for (key, position, source) in self.pairs(Self::text)? {
+                    if key != "$" {
+                        definition.references.push((key.clone(), position));
+                    }
+                    definition.update(|b| b.add_mapping(&key, &source));
+                }
+            }
+            "STYLE" => {
+                self.skip_blanks();
+                let position = self.position();
+                let style: StyleJson = self.json()?;
+                for attribute in &style.ordered_properties {
+                    definition.references.push((attribute.clone(), position));
+                }
+                definition.update(|b| b.style(style));
+            }
+            _ => return Err(error_at(position, format!("unknown object {object}"))),
+        }
+        Ok(())
+    }
+
+    /// A serde enum such as [`Encoding`](crate::models::Encoding) by its serialized name.
+    fn enum_value<T: DeserializeOwned>(&mut self) -> Result<T, OcaFileError> {
+        self.skip_blanks();
+        let position = self.position();
+        let value = self.text()?;
+        serde_json::from_value(Value::String(value.clone()))
+            .map_err(|_| error_at(position, format!("invalid value {value}")))
+    }
+}
+
+fn error_at((line, column): Position, message: String) -> OcaFileError {
+    OcaFileError {
+        line,
+        column,
+        message,
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(raw: &str, (line, column): Position) -> Result<T, OcaFileError> {
+    serde_json::from_str(raw).map_err(|e| {
+        // serde reports positions relative to the value
+        let (line, column) = if e.line() <= 1 {
+            (line, column + e.column().saturating_sub(1))
+        } else {
+            (line + e.line() - 1, e.column())
+        };
+        OcaFileError {
+            line,
+            column,
+            message: e.to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ocafile, to_ocafile, OcaFileError};
+    use crate::{error::OcaError, models::Entry, oca::generate_json_bundle};
+
+    const OCAFILE: &str = r#"-- identity card
+ADD CLASSIFICATION GICS:45102010
+ADD ATTRIBUTE givenName=Text dateOfBirth=DateTime sex=Text
+ADD FLAGGED_ATTRIBUTES dateOfBirth
+ADD META en PROPS name="Identity card" description="Who you are"
+ADD LABEL en ATTRS givenName="Given name" \
+    dateOfBirth="Date of birth" -- comments work after statements
+ADD LABEL de ATTRS givenName=Vorname
+ADD LABEL en ATTRS sex=Sex
+ADD FORMAT ATTRS dateOfBirth="%Y%m%d"
+ADD CONFORMANCE ATTRS givenName=M sex=O
+ADD ENTRY_CODE ATTRS sex=["F", "M"]
+ADD ENTRY en ATTRS sex={"F": "female", "M": "male"}
+ADD UNIT ATTRS dateOfBirth=day
+"#;
+
+    #[test]
+    fn parse_and_print() {
+        let oca = parse_ocafile(OCAFILE).unwrap();
+        assert_eq!(oca.capture_base().classification(), Some("GICS:45102010"));
+        assert_eq!(oca.attributes().len(), 3);
+        assert!(oca.capture_base().is_flagged("dateOfBirth"));
+        let names = oca
+            .overlays()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "meta (en)",
+                "label (en)",
+                "label (de)",
+                "format",
+                "conformance",
+                "entry code",
+                "entry (en)",
+                "unit"
+            ]
+        );
+        let label = oca.label("en").unwrap();
+        assert_eq!(label.label_for("sex"), Some("Sex"));
+        assert_eq!(label.label_for("dateOfBirth"), Some("Date of birth"));
+        let entry = oca.overlays().first_of::<Entry>().unwrap();
+        assert_eq!(entry.entry_for("sex", "F"), Some("female"));
+
+        let printed = to_ocafile(&oca);
+        let reparsed = parse_ocafile(&printed).unwrap();
+        assert_eq!(
+            generate_json_bundle(oca).unwrap(),
+            generate_json_bundle(reparsed).unwrap()
+        );
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let error = |source: &str| match parse_ocafile(source) {
+            Err(OcaError::OcaFile(error)) => error,
+            other => panic!("expected an OCAfile error, got {other:?}"),
+        };
+        assert_eq!(
+            error("ADD ATTRIBUTE a=Text\nADD LABEL en ATTRS b=B"),
+            OcaFileError {
+                line: 2,
+                column: 20,
+                message: "unknown attribute b".into()
+            }
+        );
+        let e = error("ADD ATTRIBUTE a=Text\nADD LABEL en ATTRS a=\"open");
+        assert_eq!((e.line, e.column), (2, 22));
+        let e = error("ADD ATTRIBUTE a=Text\n  ADD CONFORMANCE ATTRS a=X");
+        assert_eq!((e.line, e.column), (2, 27));
+        let e = error("ADD ATTRIBUTE a=Text\nADD ENTRY_CODE ATTRS a=[\"x\",]");
+        assert_eq!((e.line, e.column), (2, 29));
+        let e = error("ADD OVERLAY x");
+        assert_eq!(
+            (e.line, e.column, e.message.as_str()),
+            (1, 5, "unknown object OVERLAY")
+        );
+    }
+}
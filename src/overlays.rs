@@ -0,0 +1,237 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{models::OcaLayer, said::Said};
+
+/// A typed overlay that can be looked up with [`Overlays::overlays_of`].
+pub trait TypedOverlay: 'static {
+    /// The overlay type without its version, e.g. `spec/overlays/label`.
+    const OVERLAY_TYPE: &'static str;
+    fn from_layer(layer: &OcaLayer) -> Option<&Self>;
+}
+
+#[macro_export]
+macro_rules! impl_typed_overlay {
+    ($what:ident, $overlay_type:literal) => {
+        impl $crate::overlays::TypedOverlay for $what {
+            const OVERLAY_TYPE: &'static str = $overlay_type;
+            fn from_layer(layer: &$crate::models::OcaLayer) -> Option<&Self> {
+                match layer {
+                    $crate::models::OcaLayer::$what(overlay) => Some(overlay),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+/// Strips the version from an overlay type, `spec/overlays/label/1.0` becomes
+/// `spec/overlays/label`.
+pub fn unversioned_type(overlay_type: &str) -> &str {
+    match overlay_type.rsplit_once('/') {
+        Some((base, version))
+            if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit() || c == '.') =>
+        {
+            base
+        }
+        _ => overlay_type,
+    }
+}
+
+/// The languages to try, most specific first, when looking for `language`.
+///
+/// `de-CH` yields `de-CH`, `de` and finally `en`.
+pub fn language_fallbacks(language: &str) -> Vec<String> {
+    let mut fallbacks = vec![];
+    let mut current = language;
+    while !current.is_empty() {
+        fallbacks.push(current.to_string());
+        current = current.rsplit_once(['-', '_']).map_or("", |(head, _)| head);
+    }
+    if !fallbacks.iter().any(|l| l.eq_ignore_ascii_case("en")) {
+        fallbacks.push("en".to_string());
+    }
+    fallbacks
+}
+
+/// The overlays of a bundle, in bundle order, indexed by overlay type.
+///
+/// Serializes as a list of `[name, overlay]` pairs.
+#[derive(Debug, Clone, Default)]
+pub struct Overlays {
+    entries: Vec<(String, OcaLayer)>,
+    by_type: BTreeMap<String, Vec<usize>>,
+    /// The documents parsed overlays were read from, by digest.
+    sources: BTreeMap<String, Value>,
+}
+
+impl Overlays {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, name: String, layer: OcaLayer) {
+        self.by_type
+            .entry(unversioned_type(layer.overlay_type()).to_string())
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push((name, layer));
+    }
+    /// Adds an overlay parsed from `source`, which is written back instead of
+    /// the model as long as the two match.
+    pub(crate) fn push_parsed(&mut self, name: String, layer: OcaLayer, source: Value) {
+        self.sources.insert(layer.digest().to_string(), source);
+        self.push(name, layer);
+    }
+    /// The document the overlay with `digest` was parsed from.
+    pub(crate) fn source(&self, digest: &str) -> Option<&Value> {
+        self.sources.get(digest)
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn as_slice(&self) -> &[(String, OcaLayer)] {
+        &self.entries
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, OcaLayer)> {
+        self.entries.iter()
+    }
+    /// Looks up an overlay by its name in `meta.json`, e.g. `label (en)`.
+    pub fn get(&self, name: &str) -> Option<&OcaLayer> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, layer)| layer)
+    }
+    /// All overlays of `overlay_type`, with or without version suffix.
+    pub fn of_type<'a>(&'a self, overlay_type: &str) -> impl Iterator<Item = &'a OcaLayer> {
+        self.by_type
+            .get(unversioned_type(overlay_type))
+            .into_iter()
+            .flatten()
+            .map(|index| &self.entries[*index].1)
+    }
+    pub fn overlays_of<T: TypedOverlay>(&self) -> impl Iterator<Item = &T> {
+        self.of_type(T::OVERLAY_TYPE).filter_map(T::from_layer)
+    }
+    pub fn first_of<T: TypedOverlay>(&self) -> Option<&T> {
+        self.overlays_of::<T>().next()
+    }
+    /// The overlay of type `T` for exactly `language`, ignoring case.
+    pub fn for_exact_language<T: TypedOverlay>(&self, language: &str) -> Option<&T> {
+        self.of_type(T::OVERLAY_TYPE)
+            .filter(|layer| {
+                layer
+                    .language()
+                    .is_some_and(|l| l.eq_ignore_ascii_case(language))
+            })
+            .find_map(T::from_layer)
+    }
+    /// The overlay of type `T` best matching `language`, following
+    /// [`language_fallbacks`] and then falling back to the first overlay of that
+    /// type.
+    pub fn for_language<T: TypedOverlay>(&self, language: &str) -> Option<&T> {
+        language_fallbacks(language)
+            .iter()
+            .find_map(|candidate| self.for_exact_language(candidate))
+            .or_else(|| self.first_of())
+    }
+}
+
+impl From<Vec<(String, OcaLayer)>> for Overlays {
+    fn from(entries: Vec<(String, OcaLayer)>) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl FromIterator<(String, OcaLayer)> for Overlays {
+    fn from_iter<I: IntoIterator<Item = (String, OcaLayer)>>(iter: I) -> Self {
+        let mut overlays = Overlays::new();
+        for (name, layer) in iter {
+            overlays.push(name, layer);
+        }
+        overlays
+    }
+}
+
+impl IntoIterator for Overlays {
+    type Item = (String, OcaLayer);
+    type IntoIter = std::vec::IntoIter<(String, OcaLayer)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Overlays {
+    type Item = &'a (String, OcaLayer);
+    type IntoIter = std::slice::Iter<'a, (String, OcaLayer)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl Serialize for Overlays {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Overlays {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<(String, OcaLayer)>::deserialize(deserializer)?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{language_fallbacks, unversioned_type, Overlays};
+    use crate::models::{Format, Label, OcaLayer};
+
+    fn label(language: &str) -> (String, OcaLayer) {
+        (
+            format!("label ({language})"),
+            OcaLayer::new_label_layer("E", language, BTreeMap::new(), vec![], BTreeMap::new()),
+        )
+    }
+
+    #[test]
+    fn language_fallback() {
+        assert_eq!(language_fallbacks("de-CH"), vec!["de-CH", "de", "en"]);
+        assert_eq!(language_fallbacks("en"), vec!["en"]);
+        assert_eq!(
+            unversioned_type("spec/overlays/label/1.1"),
+            "spec/overlays/label"
+        );
+
+        let overlays = Overlays::from(vec![
+            label("en"),
+            (
+                "format".into(),
+                OcaLayer::new_format_layer("E", BTreeMap::new()),
+            ),
+            label("de"),
+            label("fr-CH"),
+        ]);
+        assert_eq!(overlays.overlays_of::<Label>().count(), 3);
+        assert_eq!(overlays.overlays_of::<Format>().count(), 1);
+        let language = |lang: &str| overlays.for_language::<Label>(lang).unwrap().language();
+        assert_eq!(language("de-CH"), "de");
+        assert_eq!(language("DE"), "de");
+        assert_eq!(language("fr-CH"), "fr-CH");
+        assert_eq!(language("fr"), "en");
+        assert_eq!(language("it"), "en");
+        assert!(overlays.for_exact_language::<Label>("it").is_none());
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use serde_json::Value;
+
+/// How values of flagged and sensitive attributes are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Redaction {
+    /// Values are shown as they are.
+    #[default]
+    None,
+    /// Values are replaced by `••••`.
+    Mask,
+    /// Only the last quarter of a value, at most four characters, is shown.
+    Partial,
+    /// The attribute is left out entirely.
+    Omit,
+}
+
+/// What masked values are replaced with.
+pub const MASK: &str = "••••";
+
+impl Redaction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Redaction::None => "none",
+            Redaction::Mask => "mask",
+            Redaction::Partial => "partial",
+            Redaction::Omit => "omit",
+        }
+    }
+
+    /// The text to show for `value`, or `None` if the attribute is omitted.
+    pub fn apply(self, value: &str) -> Option<String> {
+        match self {
+            Redaction::None => Some(value.to_string()),
+            Redaction::Mask => Some(MASK.to_string()),
+            Redaction::Partial => {
+                let count = value.chars().count();
+                let shown = (count / 4).min(4);
+                let tail: String = value.chars().skip(count - shown).collect();
+                Some(format!("{MASK}{tail}"))
+            }
+            Redaction::Omit => None,
+        }
+    }
+
+    /// Like [`Redaction::apply`] for a JSON value. Only strings and numbers are
+    /// partially shown, other values such as lists are masked as a whole, or
+    /// JSON encoded if nothing is redacted.
+    pub fn apply_value(self, value: &Value) -> Option<String> {
+        match value {
+            Value::String(text) => self.apply(text),
+            Value::Number(number) => self.apply(&number.to_string()),
+            _ if self == Redaction::Partial => Redaction::Mask.apply(""),
+            other => self.apply(&other.to_string()),
+        }
+    }
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Redaction::None),
+            "mask" => Ok(Redaction::Mask),
+            "partial" => Ok(Redaction::Partial),
+            "omit" => Ok(Redaction::Omit),
+            _ => Err(format!("unknown redaction {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Redaction;
+
+    #[test]
+    fn policies() {
+        assert_eq!(
+            Redaction::None.apply("19640812").as_deref(),
+            Some("19640812")
+        );
+        assert_eq!(Redaction::Mask.apply("19640812").as_deref(), Some("••••"));
+        assert_eq!(
+            Redaction::Partial.apply("19640812").as_deref(),
+            Some("••••12")
+        );
+        assert_eq!(Redaction::Partial.apply("Eva").as_deref(), Some("••••"));
+        assert_eq!(
+            Redaction::Partial
+                .apply("CH-1234-5678-9012-3456")
+                .as_deref(),
+            Some("••••3456")
+        );
+        assert_eq!(Redaction::Omit.apply("19640812"), None);
+        for redaction in [
+            Redaction::None,
+            Redaction::Mask,
+            Redaction::Partial,
+            Redaction::Omit,
+        ] {
+            assert_eq!(redaction.as_str().parse(), Ok(redaction));
+        }
+    }
+
+    #[test]
+    fn json_values() {
+        assert_eq!(
+            Redaction::Partial
+                .apply_value(&json!("19640812"))
+                .as_deref(),
+            Some("••••12")
+        );
+        assert_eq!(
+            Redaction::Partial.apply_value(&json!(19640812)).as_deref(),
+            Some("••••12")
+        );
+        let list = json!(["CH", "DE"]);
+        assert_eq!(
+            Redaction::Partial.apply_value(&list).as_deref(),
+            Some("••••")
+        );
+        assert_eq!(Redaction::Mask.apply_value(&list).as_deref(), Some("••••"));
+        assert_eq!(
+            Redaction::None.apply_value(&list).as_deref(),
+            Some(r#"["CH","DE"]"#)
+        );
+        assert_eq!(Redaction::Omit.apply_value(&json!({ "a": 1 })), None);
+    }
+}
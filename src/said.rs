@@ -1,15 +1,23 @@
 // Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
-// 
+//
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::io::Read;
+use std::{io::Read, ops::Range};
 
 use base64::Engine;
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use serde_json::Value;
+use sha2::Sha256;
+use sha3::Sha3_256;
 use zip::read::ZipFile;
 
-use crate::models::{CaptureBase, OcaLayer};
+use crate::{
+    error::OcaError,
+    match_layer,
+    models::{CaptureBase, OcaLayer},
+};
 
 pub trait Said {
     fn set_digest(&mut self, digest: &str);
@@ -18,71 +26,254 @@ pub trait Said {
 
 impl Said for OcaLayer {
     fn set_digest(&mut self, digest: &str) {
-        match self {
-            OcaLayer::CharacterEncoding(character_encoding) => {
-                character_encoding.set_digest(digest);
-            }
-            OcaLayer::Label(label) => label.set_digest(digest),
-            OcaLayer::Conformance(conformance) => conformance.set_digest(digest),
-            OcaLayer::Format(format) => format.set_digest(digest),
-            OcaLayer::Style(style) => style.set_digest(digest),
-            OcaLayer::AttributeMapping(attribute_mapping) => attribute_mapping.set_digest(digest),
-            OcaLayer::Other(..) => {}
-        }
+        match_layer!(self, overlay => overlay.set_digest(digest), _value => {})
     }
 
     fn digest(&self) -> &str {
+        match_layer!(self, overlay => overlay.digest(), value => value
+            .get("digest")
+            .and_then(|digest| digest.as_str())
+            .unwrap_or(""))
+    }
+}
+
+/// Length of a SAID of any of the [`SaidAlgorithm`]s, including the derivation code.
+pub const SAID_LENGTH: usize = 44;
+
+/// The dummy a SAID field holds while the SAID is calculated.
+pub fn said_placeholder() -> String {
+    "#".repeat(SAID_LENGTH)
+}
+
+/// The digest algorithms a SAID can be derived with, identified by their CESR
+/// derivation code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaidAlgorithm {
+    /// Blake3-256, code `E`.
+    #[default]
+    Blake3_256,
+    /// Blake2b-256, code `F`.
+    Blake2b256,
+    /// SHA3-256, code `H`.
+    Sha3_256,
+    /// SHA2-256, code `I`.
+    Sha2_256,
+}
+
+impl SaidAlgorithm {
+    pub fn code(self) -> char {
         match self {
-            OcaLayer::CharacterEncoding(character_encoding) => character_encoding.digest(),
-            OcaLayer::Label(label) => label.digest(),
-            OcaLayer::Conformance(conformance) => conformance.digest(),
-            OcaLayer::Format(format) => format.digest(),
-            OcaLayer::Style(style) => style.digest(),
-            OcaLayer::AttributeMapping(attribute_mapping) => attribute_mapping.digest(),
-            OcaLayer::Other(value) => value.get("digest").unwrap().as_str().unwrap(),
+            SaidAlgorithm::Blake3_256 => 'E',
+            SaidAlgorithm::Blake2b256 => 'F',
+            SaidAlgorithm::Sha3_256 => 'H',
+            SaidAlgorithm::Sha2_256 => 'I',
+        }
+    }
+    pub fn from_code(code: char) -> Option<Self> {
+        match code {
+            'E' => Some(SaidAlgorithm::Blake3_256),
+            'F' => Some(SaidAlgorithm::Blake2b256),
+            'H' => Some(SaidAlgorithm::Sha3_256),
+            'I' => Some(SaidAlgorithm::Sha2_256),
+            _ => None,
+        }
+    }
+    /// The algorithm `said` was derived with, according to its prefix.
+    pub fn from_said(said: &str) -> Option<Self> {
+        said.chars().next().and_then(Self::from_code)
+    }
+    fn digest(self, input: &[u8]) -> [u8; 32] {
+        match self {
+            SaidAlgorithm::Blake3_256 => *blake3::hash(input).as_bytes(),
+            SaidAlgorithm::Blake2b256 => Blake2b::<U32>::digest(input).into(),
+            SaidAlgorithm::Sha3_256 => Sha3_256::digest(input).into(),
+            SaidAlgorithm::Sha2_256 => Sha256::digest(input).into(),
         }
     }
 }
 
 impl OcaLayer {
-    pub fn update_digest(&mut self) -> Result<(), String> {
-        self.set_digest(&(0..44).map(|_| '#').collect::<String>());
-        let result = serde_json::to_string(&self).map_err(|e| format!("{e}"))?;
-        let result = calculate_said(&result);
+    /// Recalculates the digest, keeping the algorithm of the current one.
+    pub fn update_digest(&mut self) -> Result<(), OcaError> {
+        self.update_digest_with(SaidAlgorithm::from_said(self.digest()).unwrap_or_default())
+    }
+    pub fn update_digest_with(&mut self, algorithm: SaidAlgorithm) -> Result<(), OcaError> {
+        self.set_digest(&said_placeholder());
+        let result = serde_json::to_string(&self).map_err(OcaError::Serialization)?;
+        let result = calculate_said_with(&result, algorithm);
         self.set_digest(&result);
         Ok(())
     }
 }
 impl CaptureBase {
-    pub fn update_digest(&mut self) -> Result<(), String> {
-        self.digest = (0..44).map(|_| '#').collect::<String>();
-        let result = serde_json::to_string(&self).map_err(|e| format!("{e}"))?;
-        let result = calculate_said(&result);
+    /// Recalculates the digest, keeping the algorithm of the current one.
+    pub fn update_digest(&mut self) -> Result<(), OcaError> {
+        self.update_digest_with(SaidAlgorithm::from_said(&self.digest).unwrap_or_default())
+    }
+    pub fn update_digest_with(&mut self, algorithm: SaidAlgorithm) -> Result<(), OcaError> {
+        self.digest = said_placeholder();
+        let result = serde_json::to_string(&self).map_err(OcaError::Serialization)?;
+        let result = calculate_said_with(&result, algorithm);
         self.digest = result;
         Ok(())
     }
 }
 
-pub fn verify_said(said: &str, mut file: ZipFile) -> Result<bool, String> {
+/// Reads `file` and checks that it hashes to `said`, also accepting the legacy
+/// Blake3 encoding if `accept_legacy` is set.
+pub fn verify_said(said: &str, mut file: ZipFile, accept_legacy: bool) -> Result<(), OcaError> {
     let mut json_representation = String::new();
-    file.read_to_string(&mut json_representation)
-        .map_err(|e| format!("{e}"))?;
-    verify_said_from_str(said, &json_representation)
-}
-pub fn verify_said_from_str(said: &str, hash_input: &str) -> Result<bool, String> {
-    let hash_input = hash_input.replace(said, &(0..said.len()).map(|_| '#').collect::<String>());
-    let hash_input = hash_input.as_bytes();
-    let digest_result = blake3::hash(hash_input);
-    let said_calculated = base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(digest_result.as_bytes());
-    let said_calculated = format!("E{said_calculated}");
-    Ok(said_calculated == said)
+    file.read_to_string(&mut json_representation)?;
+    let matches = verify_said_from_str(said, &json_representation)?
+        || (accept_legacy && verify_legacy_said_from_str(said, &json_representation));
+    if !matches {
+        return Err(OcaError::SaidMismatch {
+            expected: said.to_string(),
+            actual: said_from_str(said, &json_representation),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that `hash_input`, a JSON document containing `said`, hashes to `said`
+/// with the algorithm given by the derivation code of `said`.
+pub fn verify_said_from_str(said: &str, hash_input: &str) -> Result<bool, OcaError> {
+    SaidAlgorithm::from_said(said)
+        .ok_or_else(|| OcaError::UnknownDerivationCode(said.to_string()))?;
+    Ok(said_from_str(said, hash_input) == said)
+}
+
+/// Checks `said` against the Blake3 encoding of earlier versions of this crate,
+/// which hashed the file as is and skipped the CESR pre-padding.
+///
+/// Only use this for bundles known to come from such versions, the encoding is
+/// not part of the SAID spec.
+pub fn verify_legacy_said_from_str(said: &str, hash_input: &str) -> bool {
+    let legacy_input = hash_input.replace(said, &"#".repeat(said.len()));
+    let digest_result = blake3::hash(legacy_input.as_bytes());
+    let legacy = base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(digest_result.as_bytes());
+    format!("E{legacy}") == said
+}
+
+/// Calculates the SAID of `hash_input`, assuming its digest field currently holds
+/// `said`, with the algorithm `said` was derived with.
+pub fn said_from_str(said: &str, hash_input: &str) -> String {
+    calculate_said_with(
+        &canonical_said_input(said, hash_input),
+        SaidAlgorithm::from_said(said).unwrap_or_default(),
+    )
+}
+
+/// Serializes `hash_input` the way the SAID spec hashes it: compact, in
+/// insertion order, with the field holding `said` replaced by the dummy.
+///
+/// Strings and numbers are kept exactly as written, so escapes like `\u00e9` or
+/// `\/` and number forms like `1.0e2` hash the same as in the original document.
+///
+/// The SAID field is `digest` for OCA, `d` for ACDC style documents, or else any
+/// top level field holding `said`. Input that is not a JSON object is only
+/// stripped of the SAID.
+pub fn canonical_said_input(said: &str, hash_input: &str) -> String {
+    let fallback = || hash_input.trim().replace(said, &said_placeholder());
+    if !matches!(
+        serde_json::from_str::<Value>(hash_input),
+        Ok(Value::Object(_))
+    ) {
+        return fallback();
+    }
+    let mut compact = compact_json(hash_input);
+    let fields = top_level_fields(&compact);
+    let holding_said = |label: Option<&str>| {
+        fields
+            .iter()
+            .find(|(key, value)| {
+                label.is_none_or(|label| compact[key.clone()] == *label)
+                    && compact[value.clone()] == *said
+            })
+            .map(|(_, value)| value.clone())
+    };
+    match holding_said(Some("digest"))
+        .or_else(|| holding_said(Some("d")))
+        .or_else(|| holding_said(None))
+    {
+        Some(span) => {
+            compact.replace_range(span, &said_placeholder());
+            compact
+        }
+        None => compact,
+    }
+}
+
+/// Drops the whitespace between the tokens of the JSON text `json`.
+fn compact_json(json: &str) -> String {
+    let mut compact = String::with_capacity(json.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for c in json.chars() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else if c.is_ascii_whitespace() {
+            continue;
+        } else {
+            in_string = c == '"';
+        }
+        compact.push(c);
+    }
+    compact
 }
+
+/// The byte ranges of the raw key and string value of each top level field of
+/// the compact JSON object `json` whose value is a string, quotes excluded.
+fn top_level_fields(json: &str) -> Vec<(Range<usize>, Range<usize>)> {
+    let bytes = json.as_bytes();
+    let mut fields = vec![];
+    let (mut depth, mut expect_key, mut key) = (0, false, None);
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let mut end = i + 1;
+                while bytes[end] != b'"' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                if depth == 1 && expect_key {
+                    key = Some(i + 1..end);
+                    expect_key = false;
+                } else if depth == 1 {
+                    fields.extend(key.take().map(|key| (key, i + 1..end)));
+                }
+                i = end;
+            }
+            b'{' | b'[' => {
+                depth += 1;
+                expect_key = depth == 1;
+            }
+            b'}' | b']' => depth -= 1,
+            b',' if depth == 1 => {
+                expect_key = true;
+                key = None;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    fields
+}
+
+/// Calculates the Blake3-256 SAID of an already canonical serialization.
 pub fn calculate_said(json: &str) -> String {
-    let hash_input = json.trim().as_bytes();
-    let digest_result = blake3::hash(hash_input);
-    let said_calculated = base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(digest_result.as_bytes());
-    let said_calculated = format!("E{said_calculated}");
-    said_calculated
+    calculate_said_with(json, SaidAlgorithm::Blake3_256)
+}
+
+/// Calculates the SAID of an already canonical serialization, CESR encoded with
+/// the derivation code of `algorithm`.
+pub fn calculate_said_with(json: &str, algorithm: SaidAlgorithm) -> String {
+    let digest_result = algorithm.digest(json.trim().as_bytes());
+    // CESR pre-pads the digest so the code replaces whole base64 characters
+    let mut padded = vec![0u8];
+    padded.extend_from_slice(&digest_result);
+    let encoded = base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(padded);
+    format!("{}{}", algorithm.code(), &encoded[1..])
 }
 
 #[macro_export]
@@ -97,4 +288,137 @@ macro_rules! impl_said {
             }
         }
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calculate_said, calculate_said_with, canonical_said_input, verify_legacy_said_from_str,
+        verify_said_from_str, SaidAlgorithm,
+    };
+    use serde_json::Value;
+
+    // Example from the SAID specification (draft-ssmith-said)
+    const SPEC_EXAMPLE: &str = r#"{
+    "said": "EJymtAC4piy_HkHWRs4JSRv0sb53MZJr8BQ4SMixXIVJ",
+    "first": "Sue",
+    "last": "Smith",
+    "role": "Founder"
+}"#;
+    const SPEC_SAID: &str = "EJymtAC4piy_HkHWRs4JSRv0sb53MZJr8BQ4SMixXIVJ";
+    // The same example as published in earlier drafts, without CESR pre-padding
+    const LEGACY_SAID: &str = "EnKa0ALimLL8eQdZGzglJG_SxvncxkmvwFDhIyLFchUk";
+
+    #[test]
+    fn spec_example() {
+        let canonical = canonical_said_input(SPEC_SAID, SPEC_EXAMPLE);
+        assert_eq!(
+            canonical,
+            format!(
+                r#"{{"said":"{}","first":"Sue","last":"Smith","role":"Founder"}}"#,
+                "#".repeat(44)
+            )
+        );
+        assert_eq!(calculate_said(&canonical), SPEC_SAID);
+        assert!(verify_said_from_str(SPEC_SAID, SPEC_EXAMPLE).unwrap());
+    }
+
+    #[test]
+    fn field_order_matters_whitespace_does_not() {
+        let compact = SPEC_EXAMPLE.replace(['\n', ' '], "");
+        assert!(verify_said_from_str(SPEC_SAID, &compact).unwrap());
+        let reordered =
+            format!(r#"{{"first":"Sue","said":"{SPEC_SAID}","last":"Smith","role":"Founder"}}"#);
+        assert!(!verify_said_from_str(SPEC_SAID, &reordered).unwrap());
+    }
+
+    #[test]
+    fn legacy_encoding() {
+        let legacy = SPEC_EXAMPLE
+            .replace(SPEC_SAID, LEGACY_SAID)
+            .replace(['\n', ' '], "");
+        assert!(!verify_said_from_str(LEGACY_SAID, &legacy).unwrap());
+        assert!(verify_legacy_said_from_str(LEGACY_SAID, &legacy));
+        assert!(!verify_legacy_said_from_str(SPEC_SAID, SPEC_EXAMPLE));
+    }
+
+    // Bundles and overlays published with the test suite of the Human Colossus
+    // oca-bundle crate (version 2.0.0), each document carrying its own SAID
+    const PUBLISHED: [&str; 3] = [
+        include_str!("../fixtures/said/entrance_credential.json"),
+        include_str!("../fixtures/said/entrance_credential_conformance_list.json"),
+        include_str!("../fixtures/said/passport_label.json"),
+    ];
+
+    fn assert_verifies(document: &Value) {
+        let said = document["digest"].as_str().unwrap();
+        assert!(
+            verify_said_from_str(said, &document.to_string()).unwrap(),
+            "{said}"
+        );
+    }
+
+    #[test]
+    fn published_documents() {
+        for published in PUBLISHED {
+            let document: Value = serde_json::from_str(published).unwrap();
+            assert!(verify_said_from_str(document["digest"].as_str().unwrap(), published).unwrap());
+            assert_verifies(&document);
+            if let Some(capture_base) = document.get("capture_base").filter(|c| c.is_object()) {
+                assert_verifies(capture_base);
+            }
+            for overlay in document["overlays"].as_array().into_iter().flatten() {
+                assert_verifies(overlay);
+            }
+        }
+        let tampered = PUBLISHED[2].replace("Passport Number", "Passport number");
+        let document: Value = serde_json::from_str(&tampered).unwrap();
+        assert!(!verify_said_from_str(document["digest"].as_str().unwrap(), &tampered).unwrap());
+    }
+
+    #[test]
+    fn escapes_and_number_forms() {
+        // escaped unicode and slashes come out differently when re-serialized
+        let escaped = include_str!("../fixtures/said/escaped_meta.json");
+        let document: Value = serde_json::from_str(escaped).unwrap();
+        let said = document["digest"].as_str().unwrap();
+        assert!(verify_said_from_str(said, escaped).unwrap());
+        assert!(!verify_said_from_str(said, &document.to_string()).unwrap());
+        let tampered = escaped.replace(r"\u00e9lectronique", r"\u00c9lectronique");
+        assert!(!verify_said_from_str(said, &tampered).unwrap());
+
+        let placeholder = "#".repeat(44);
+        let template = format!(r#"{{"d": "{placeholder}", "height": 1.0e2, "unit": "cm"}}"#);
+        assert_eq!(
+            canonical_said_input(&placeholder, &template),
+            format!(r#"{{"d":"{placeholder}","height":1.0e2,"unit":"cm"}}"#)
+        );
+        let said = calculate_said(&canonical_said_input(&placeholder, &template));
+        let document = template.replace(&placeholder, &said);
+        assert!(verify_said_from_str(&said, &document).unwrap());
+        assert!(!verify_said_from_str(&said, &document.replace("1.0e2", "100.0")).unwrap());
+    }
+
+    #[test]
+    fn algorithms() {
+        let placeholder = "#".repeat(44);
+        let template = SPEC_EXAMPLE.replace(SPEC_SAID, &placeholder);
+        for algorithm in [
+            SaidAlgorithm::Blake3_256,
+            SaidAlgorithm::Blake2b256,
+            SaidAlgorithm::Sha3_256,
+            SaidAlgorithm::Sha2_256,
+        ] {
+            let said =
+                calculate_said_with(&canonical_said_input(&placeholder, &template), algorithm);
+            assert_eq!(said.len(), 44);
+            assert_eq!(SaidAlgorithm::from_said(&said), Some(algorithm));
+            let document = template.replace(&placeholder, &said);
+            assert!(verify_said_from_str(&said, &document).unwrap());
+        }
+        let mut other = SPEC_SAID.to_string();
+        other.replace_range(..1, "I");
+        assert!(!verify_said_from_str(&other, &SPEC_EXAMPLE.replace(SPEC_SAID, &other)).unwrap());
+        assert!(verify_said_from_str("X", SPEC_EXAMPLE).is_err());
+    }
+}
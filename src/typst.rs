@@ -8,7 +8,12 @@ use base64::Engine;
 use chrono::{NaiveDate, NaiveDateTime};
 use wasm_minimal_protocol::{initiate_protocol, wasm_func};
 
-use crate::{models::AttributeMapping, oca::parse_zip};
+use crate::{
+    models::{AttributeMapping, Oca},
+    oca::parse_zip,
+    redaction::Redaction,
+    units::{format_measurement, MeasurementSystem},
+};
 
 initiate_protocol!();
 
@@ -28,10 +33,52 @@ pub fn format_date(date: &[u8], fmt_string: &[u8]) -> Result<Vec<u8>, String> {
 
 #[wasm_func]
 pub fn get_oca(file: &[u8]) -> Result<Vec<u8>, String> {
-    let oca = parse_zip(file)?;
+    let oca = parse_zip(file).map_err(|e| format!("{e}"))?;
     Ok(serde_json::to_string(&oca).unwrap().as_bytes().to_vec())
 }
 #[wasm_func]
+pub fn label_for_language(oca: &[u8], language: &[u8]) -> Result<Vec<u8>, String> {
+    let oca: Oca = serde_json::from_slice(oca).map_err(|e| format!("{e}"))?;
+    let language = std::str::from_utf8(language).map_err(|e| format!("{e}"))?;
+    serde_json::to_vec(&oca.label_for_language(language)).map_err(|e| format!("{e}"))
+}
+#[wasm_func]
+pub fn entry_for_language(oca: &[u8], language: &[u8]) -> Result<Vec<u8>, String> {
+    let oca: Oca = serde_json::from_slice(oca).map_err(|e| format!("{e}"))?;
+    let language = std::str::from_utf8(language).map_err(|e| format!("{e}"))?;
+    serde_json::to_vec(&oca.entry_for_language(language)).map_err(|e| format!("{e}"))
+}
+/// Formats `value` with `unit` of the unit overlay's `metric_system`, converted
+/// to the measurement system `system` names, or else the one customary for the
+/// locale it names.
+#[wasm_func]
+pub fn format_unit(
+    value: &[u8],
+    unit: &[u8],
+    metric_system: &[u8],
+    system: &[u8],
+) -> Result<Vec<u8>, String> {
+    let value = std::str::from_utf8(value).map_err(|e| format!("{e}"))?;
+    let value: f64 = value.trim().parse().map_err(|e| format!("{e}"))?;
+    let unit = std::str::from_utf8(unit).map_err(|e| format!("{e}"))?;
+    let metric_system = std::str::from_utf8(metric_system).map_err(|e| format!("{e}"))?;
+    let system = std::str::from_utf8(system).map_err(|e| format!("{e}"))?;
+    let system = system
+        .parse()
+        .unwrap_or_else(|_| MeasurementSystem::for_locale(system));
+    Ok(format_measurement(value, unit, metric_system, Some(system)).into_bytes())
+}
+/// Redacts the JSON encoded `value` according to the redaction policy named
+/// `policy`, returning nothing if the value is to be omitted.
+#[wasm_func]
+pub fn redact(value: &[u8], policy: &[u8]) -> Result<Vec<u8>, String> {
+    let value: serde_json::Value = serde_json::from_slice(value).map_err(|e| format!("{e}"))?;
+    let policy: Redaction = std::str::from_utf8(policy)
+        .map_err(|e| format!("{e}"))?
+        .parse()?;
+    Ok(policy.apply_value(&value).unwrap_or_default().into_bytes())
+}
+#[wasm_func]
 pub fn render(text: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
     let data: serde_json::Value = serde_json::from_slice(data).map_err(|e| format!("{e}"))?;
     let text = std::str::from_utf8(text).map_err(|e| format!("{e}"))?;
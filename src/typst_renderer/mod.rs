@@ -11,7 +11,7 @@ use serde_json::Value;
 use typst::{
     diag::{FileError, FileResult},
     eval::Tracer,
-    foundations::Datetime,
+    foundations::{Datetime, Dict, IntoValue},
     layout::Ratio,
     model::Document,
     syntax::{FileId, Source, VirtualPath},
@@ -20,7 +20,10 @@ use typst::{
     Library, World,
 };
 
-use crate::{models::Oca, oca::generate_zip};
+use crate::{
+    credential::Credential, models::Oca, oca::generate_zip, redaction::Redaction,
+    units::MeasurementSystem,
+};
 
 pub fn load_fonts() -> (FontBook, Vec<Font>) {
     let mut fonts = vec![];
@@ -75,6 +78,9 @@ pub struct TypstWorld {
     fonts: Vec<Font>,
     json: Value,
     oca: Oca,
+    language: String,
+    redaction: Redaction,
+    units: Option<MeasurementSystem>,
 }
 struct Slot {
     fingerprint: u128,
@@ -85,9 +91,11 @@ struct Slot {
 impl TypstWorld {
     pub fn new(root: String, json: Value, oca: Oca) -> Self {
         let (book, fonts) = load_fonts();
+        let language = "en".to_string();
+        let redaction = Redaction::default();
         Self {
             root,
-            library: Prehashed::new(Library::builder().build()),
+            library: Prehashed::new(build_library(&language, redaction, None)),
             main_id: FileId::new(None, VirtualPath::new("main.typ")),
             book: Prehashed::new(book),
             sources: Mutex::new(HashMap::new()),
@@ -95,9 +103,42 @@ impl TypstWorld {
             fonts,
             json,
             oca,
+            language,
+            redaction,
+            units: None,
         }
     }
 
+    /// A world rendering the claims of a decoded JWT-VC or SD-JWT, only what the
+    /// holder disclosed.
+    pub fn from_credential(root: String, credential: &Credential, oca: Oca) -> Self {
+        Self::new(root, credential.claims().clone(), oca)
+    }
+
+    /// Selects the language used for labels, falling back as described in
+    /// [`crate::overlays::language_fallbacks`].
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = language.to_string();
+        self.library = Prehashed::new(build_library(&self.language, self.redaction, self.units));
+        self
+    }
+
+    /// Selects the measurement system values with a unit are converted to,
+    /// instead of the one customary for the language.
+    pub fn with_units(mut self, units: MeasurementSystem) -> Self {
+        self.units = Some(units);
+        self.library = Prehashed::new(build_library(&self.language, self.redaction, self.units));
+        self
+    }
+
+    /// Selects how attributes flagged in the capture base or listed in a
+    /// sensitive overlay are rendered, e.g. [`Redaction::Mask`] for screenshots.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self.library = Prehashed::new(build_library(&self.language, self.redaction, self.units));
+        self
+    }
+
     pub fn compile(&self) -> Result<Document, String> {
         let mut tracer = Tracer::new();
         let document = typst::compile(self, &mut tracer).unwrap();
@@ -134,8 +175,7 @@ impl TypstWorld {
             })?,
         );
         println!("pixmap rendered");
-        let Ok(png) = pixmap
-        .encode_png() else {
+        let Ok(png) = pixmap.encode_png() else {
             return Err(CompilationError::CompilationError {
                 inner: "could not render".to_string(),
             });
@@ -151,6 +191,25 @@ impl TypstWorld {
         }
     }
 }
+/// Builds the standard library, passing the render options to the template as
+/// `sys.inputs`.
+fn build_library(
+    language: &str,
+    redaction: Redaction,
+    units: Option<MeasurementSystem>,
+) -> Library {
+    let mut inputs = [
+        ("language".into(), language.into_value()),
+        ("redaction".into(), redaction.as_str().into_value()),
+    ]
+    .into_iter()
+    .collect::<Dict>();
+    if let Some(units) = units {
+        inputs.insert("units".into(), units.as_str().into_value());
+    }
+    Library::builder().with_inputs(inputs).build()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CompilationError {
     #[error("Failed to compile")]
@@ -274,3 +333,164 @@ impl World for TypstWorld {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use typst::{
+        layout::{Frame, FrameItem},
+        model::Document,
+    };
+
+    use super::TypstWorld;
+    use crate::{
+        builder::OcaBuilder,
+        models::{Oca, StyleJson},
+        redaction::Redaction,
+        units::MeasurementSystem,
+    };
+
+    fn oca() -> Oca {
+        OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_attribute("dateOfBirth", "DateTime")
+            .add_attribute("height", "Numeric")
+            .add_attribute("sex", "Text")
+            .add_attribute("documentNumber", "Text")
+            .add_attribute("nationalities", "Array[Text]")
+            .add_label("en", "givenName", "Given name")
+            .add_label("de", "givenName", "Vorname")
+            .add_entry("en", "sex", "F", "Female")
+            .add_format("dateOfBirth", "%Y%m%d")
+            .add_unit("height", "cm")
+            .flag_attribute("dateOfBirth")
+            .add_sensitive("documentNumber")
+            .add_sensitive("nationalities")
+            .add_mapping("givenName", "vc.givenName")
+            .add_mapping("dateOfBirth", "vc.dateOfBirth")
+            .add_mapping("height", "vc.height")
+            .add_mapping("sex", "vc.sex")
+            .add_mapping("documentNumber", "vc.documentNumber")
+            .add_mapping("nationalities", "vc.nationalities")
+            .style(StyleJson {
+                title: "{{givenName}}".to_string(),
+                subtitle: "{{documentNumber}}".to_string(),
+                card_color: 0xff336699,
+                text_color: "light".to_string(),
+                background_card: None,
+                ordered_properties: vec![
+                    "givenName".to_string(),
+                    "dateOfBirth".to_string(),
+                    "height".to_string(),
+                    "sex".to_string(),
+                    "documentNumber".to_string(),
+                    "nationalities".to_string(),
+                ],
+                extra: Default::default(),
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn data(sex: &str) -> Value {
+        json!({
+            "vc": {
+                "givenName": "Erika",
+                "dateOfBirth": "19640812",
+                "height": 172,
+                "sex": sex,
+                "documentNumber": "C01X00T47",
+                "nationalities": ["CH", "DE"]
+            }
+        })
+    }
+
+    /// The text runs of `page`, trimmed and separated by a space.
+    fn page_text(document: &Document, page: usize) -> String {
+        fn collect<'a>(frame: &'a Frame, runs: &mut Vec<&'a str>) {
+            for (_, item) in frame.items() {
+                match item {
+                    FrameItem::Group(group) => collect(&group.frame, runs),
+                    FrameItem::Text(text) => runs.push(text.text.trim()),
+                    _ => {}
+                }
+            }
+        }
+        let mut runs = vec![];
+        collect(&document.pages[page].frame, &mut runs);
+        runs.retain(|run| !run.is_empty());
+        runs.join(" ")
+    }
+
+    #[test]
+    fn render_smoke_test() {
+        let world = TypstWorld::new(".".to_string(), data("F"), oca());
+        let png = world.compile_png(0, 10.0).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let document = world.compile().unwrap();
+        assert_eq!(page_text(&document, 0), "Erika C01X00T47");
+        let text = page_text(&document, 1);
+        assert!(text.starts_with("Given name: Erika dateOfBirth: 1964-08-12"));
+        assert!(text.contains("sex: Female documentNumber: C01X00T47"));
+
+        let world = world
+            .with_language("de")
+            .with_units(MeasurementSystem::Imperial)
+            .with_redaction(Redaction::Mask);
+        let png = world.compile_png(1, 10.0).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let text = page_text(&world.compile().unwrap(), 1);
+        assert!(text.starts_with("Vorname: Erika"));
+    }
+
+    #[test]
+    fn entry_codes_are_localized() {
+        let oca = oca();
+        // what the template receives from the plugin
+        let entries = serde_json::to_value(oca.entry_for_language("de")).unwrap();
+        assert_eq!(entries["attribute_entries"]["sex"]["F"], "Female");
+        let labels = serde_json::to_value(oca.label_for_language("de")).unwrap();
+        assert_eq!(labels["attribute_labels"]["givenName"], "Vorname");
+        assert_eq!(oca.localized_entry("sex", "X", "en"), "X");
+
+        let world = TypstWorld::new(".".to_string(), data("F"), oca.clone());
+        let text = page_text(&world.compile().unwrap(), 1);
+        assert!(text.contains("sex: Female"));
+        // codes without an entry are shown as they are
+        let world = TypstWorld::new(".".to_string(), data("X"), oca);
+        let text = page_text(&world.compile().unwrap(), 1);
+        assert!(text.contains("sex: X documentNumber:"));
+    }
+
+    #[test]
+    fn numeric_attributes_show_their_unit() {
+        let world = TypstWorld::new(".".to_string(), data("F"), oca());
+        let text = page_text(&world.compile().unwrap(), 1);
+        assert!(text.contains("height: 172 cm sex:"));
+        let world = world.with_language("en-US");
+        let text = page_text(&world.compile().unwrap(), 1);
+        assert!(text.contains("height: 67.72 in sex:"));
+        let world = world.with_units(MeasurementSystem::Metric);
+        let text = page_text(&world.compile().unwrap(), 1);
+        assert!(text.contains("height: 172 cm sex:"));
+    }
+
+    #[test]
+    fn sensitive_attributes_are_redacted() {
+        let world = TypstWorld::new(".".to_string(), data("F"), oca());
+        let document = world.with_redaction(Redaction::Mask).compile().unwrap();
+        assert_eq!(page_text(&document, 0), "Erika ••••");
+        let text = page_text(&document, 1);
+        assert!(text.contains("dateOfBirth: •••• height:"));
+        assert!(text.contains("documentNumber: •••• nationalities: ••••"));
+        assert!(!text.contains("C01X00T47") && !text.contains("1964"));
+
+        let world = TypstWorld::new(".".to_string(), data("F"), oca());
+        let document = world.with_redaction(Redaction::Omit).compile().unwrap();
+        assert_eq!(page_text(&document, 0), "Erika");
+        assert_eq!(
+            page_text(&document, 1),
+            "Given name: Erika height: 172 cm sex: Female"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{fmt::Display, str::FromStr};
+
+/// The system measurements are displayed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementSystem {
+    Metric,
+    Imperial,
+}
+
+impl MeasurementSystem {
+    /// The system customary for `locale`, imperial for the United States,
+    /// Liberia and Myanmar and metric everywhere else.
+    pub fn for_locale(locale: &str) -> Self {
+        let mut parts = locale.split(['-', '_']);
+        let language = parts.next().unwrap_or_default();
+        let region = parts.find(|part| part.len() == 2 && part.chars().all(char::is_alphabetic));
+        match region.map(str::to_ascii_uppercase).as_deref() {
+            Some("US" | "LR" | "MM") => MeasurementSystem::Imperial,
+            Some(_) => MeasurementSystem::Metric,
+            None if language.eq_ignore_ascii_case("my") => MeasurementSystem::Imperial,
+            None => MeasurementSystem::Metric,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MeasurementSystem::Metric => "metric",
+            MeasurementSystem::Imperial => "imperial",
+        }
+    }
+}
+
+impl FromStr for MeasurementSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "metric" | "si" => Ok(MeasurementSystem::Metric),
+            "imperial" | "us" => Ok(MeasurementSystem::Imperial),
+            _ => Err(format!("unknown measurement system {s}")),
+        }
+    }
+}
+
+// unit, display symbol, system, counterpart in the other system, factor to it
+const UNITS: &[(&str, &str, MeasurementSystem, &str, f64)] = &[
+    ("mm", "mm", MeasurementSystem::Metric, "in", 1.0 / 25.4),
+    ("cm", "cm", MeasurementSystem::Metric, "in", 1.0 / 2.54),
+    ("m", "m", MeasurementSystem::Metric, "ft", 1.0 / 0.3048),
+    ("km", "km", MeasurementSystem::Metric, "mi", 1.0 / 1.609344),
+    (
+        "g",
+        "g",
+        MeasurementSystem::Metric,
+        "oz",
+        1.0 / 28.349523125,
+    ),
+    (
+        "kg",
+        "kg",
+        MeasurementSystem::Metric,
+        "lb",
+        1.0 / 0.45359237,
+    ),
+    (
+        "ml",
+        "ml",
+        MeasurementSystem::Metric,
+        "[foz_us]",
+        1.0 / 29.5735295625,
+    ),
+    (
+        "l",
+        "l",
+        MeasurementSystem::Metric,
+        "[gal_us]",
+        1.0 / 3.785411784,
+    ),
+    ("in", "in", MeasurementSystem::Imperial, "cm", 2.54),
+    ("[in_i]", "in", MeasurementSystem::Imperial, "cm", 2.54),
+    ("ft", "ft", MeasurementSystem::Imperial, "m", 0.3048),
+    ("[ft_i]", "ft", MeasurementSystem::Imperial, "m", 0.3048),
+    ("mi", "mi", MeasurementSystem::Imperial, "km", 1.609344),
+    ("[mi_i]", "mi", MeasurementSystem::Imperial, "km", 1.609344),
+    ("oz", "oz", MeasurementSystem::Imperial, "g", 28.349523125),
+    (
+        "[oz_av]",
+        "oz",
+        MeasurementSystem::Imperial,
+        "g",
+        28.349523125,
+    ),
+    ("lb", "lb", MeasurementSystem::Imperial, "kg", 0.45359237),
+    (
+        "[lb_av]",
+        "lb",
+        MeasurementSystem::Imperial,
+        "kg",
+        0.45359237,
+    ),
+    (
+        "[foz_us]",
+        "fl oz",
+        MeasurementSystem::Imperial,
+        "ml",
+        29.5735295625,
+    ),
+    (
+        "[gal_us]",
+        "gal",
+        MeasurementSystem::Imperial,
+        "l",
+        3.785411784,
+    ),
+];
+
+/// A value with a unit code from the unit overlay, e.g. `cm` or UCUM codes
+/// such as `[lb_av]` or `Cel`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub value: f64,
+    pub unit: String,
+}
+
+impl Measurement {
+    pub fn new(value: f64, unit: &str) -> Self {
+        Self {
+            value,
+            unit: unit.to_string(),
+        }
+    }
+
+    /// The system the unit belongs to, if it is a known one.
+    pub fn system(&self) -> Option<MeasurementSystem> {
+        if let Some(temperature) = Temperature::from_unit(&self.unit) {
+            return Some(temperature.system());
+        }
+        UNITS
+            .iter()
+            .find(|(unit, ..)| *unit == self.unit)
+            .map(|(_, _, system, ..)| *system)
+    }
+
+    /// Converts to the corresponding unit of `system`. Unknown units and
+    /// measurements already in `system` are returned unchanged.
+    pub fn to_system(&self, system: MeasurementSystem) -> Measurement {
+        if self.system().is_none_or(|own| own == system) {
+            return self.clone();
+        }
+        if let Some(temperature) = Temperature::from_unit(&self.unit) {
+            return temperature.convert(self.value);
+        }
+        UNITS
+            .iter()
+            .find(|(unit, ..)| *unit == self.unit)
+            .map(|(_, _, _, target, factor)| Measurement::new(self.value * factor, target))
+            .unwrap_or_else(|| self.clone())
+    }
+
+    pub fn symbol(&self) -> &str {
+        if let Some(temperature) = Temperature::from_unit(&self.unit) {
+            return temperature.symbol();
+        }
+        UNITS
+            .iter()
+            .find(|(unit, ..)| *unit == self.unit)
+            .map_or(self.unit.as_str(), |(_, symbol, ..)| symbol)
+    }
+}
+
+/// Prints the value with at most two decimals, followed by the unit symbol.
+impl Display for Measurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = format!("{:.2}", self.value);
+        let value = value.trim_end_matches('0').trim_end_matches('.');
+        let value = if value == "-0" { "0" } else { value };
+        write!(f, "{value} {}", self.symbol())
+    }
+}
+
+// temperatures are not proportional, so they are not in `UNITS`
+#[derive(Clone, Copy)]
+enum Temperature {
+    Celsius,
+    Fahrenheit,
+}
+
+impl Temperature {
+    fn from_unit(unit: &str) -> Option<Self> {
+        match unit {
+            "Cel" | "°C" => Some(Temperature::Celsius),
+            "[degF]" | "°F" => Some(Temperature::Fahrenheit),
+            _ => None,
+        }
+    }
+    fn system(self) -> MeasurementSystem {
+        match self {
+            Temperature::Celsius => MeasurementSystem::Metric,
+            Temperature::Fahrenheit => MeasurementSystem::Imperial,
+        }
+    }
+    fn symbol(self) -> &'static str {
+        match self {
+            Temperature::Celsius => "°C",
+            Temperature::Fahrenheit => "°F",
+        }
+    }
+    fn convert(self, value: f64) -> Measurement {
+        match self {
+            Temperature::Celsius => Measurement::new(value * 9.0 / 5.0 + 32.0, "°F"),
+            Temperature::Fahrenheit => Measurement::new((value - 32.0) * 5.0 / 9.0, "°C"),
+        }
+    }
+}
+
+/// Formats `value` in `unit` for display, converted to `system` if given.
+///
+/// The system the value is in is told by the unit code. `metric_system`, the one
+/// the unit overlay declares, e.g. `SI`, only stands in for unknown codes, which
+/// are never converted.
+pub fn format_measurement(
+    value: f64,
+    unit: &str,
+    metric_system: &str,
+    system: Option<MeasurementSystem>,
+) -> String {
+    let measurement = Measurement::new(value, unit);
+    let source = measurement
+        .system()
+        .or_else(|| metric_system.parse::<MeasurementSystem>().ok());
+    match system {
+        Some(system) if source != Some(system) => measurement.to_system(system).to_string(),
+        _ => measurement.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_measurement, Measurement, MeasurementSystem};
+
+    #[test]
+    fn locales() {
+        assert_eq!(
+            MeasurementSystem::for_locale("en-US"),
+            MeasurementSystem::Imperial
+        );
+        assert_eq!(
+            MeasurementSystem::for_locale("en_GB"),
+            MeasurementSystem::Metric
+        );
+        assert_eq!(
+            MeasurementSystem::for_locale("de"),
+            MeasurementSystem::Metric
+        );
+        assert_eq!(
+            "imperial".parse::<MeasurementSystem>(),
+            Ok(MeasurementSystem::Imperial)
+        );
+    }
+
+    #[test]
+    fn conversion_and_formatting() {
+        assert_eq!(format_measurement(180.0, "cm", "SI", None), "180 cm");
+        assert_eq!(
+            format_measurement(180.0, "cm", "SI", Some(MeasurementSystem::Imperial)),
+            "70.87 in"
+        );
+        assert_eq!(
+            format_measurement(180.0, "cm", "SI", Some(MeasurementSystem::Metric)),
+            "180 cm"
+        );
+        assert_eq!(
+            format_measurement(150.0, "[lb_av]", "", Some(MeasurementSystem::Metric)),
+            "68.04 kg"
+        );
+        assert_eq!(
+            format_measurement(37.5, "Cel", "SI", Some(MeasurementSystem::Imperial)),
+            "99.5 °F"
+        );
+        // the unit code wins over the system the overlay declares
+        assert_eq!(
+            format_measurement(12.0, "oz", "SI", Some(MeasurementSystem::Metric)),
+            "340.19 g"
+        );
+        assert_eq!(
+            format_measurement(12.0, "oz", "US", Some(MeasurementSystem::Metric)),
+            "340.19 g"
+        );
+        assert_eq!(
+            format_measurement(3.0, "mmol/L", "US", Some(MeasurementSystem::Metric)),
+            "3 mmol/L"
+        );
+        let unknown = Measurement::new(3.0, "mmol/L");
+        assert_eq!(unknown.to_system(MeasurementSystem::Imperial), unknown);
+        assert_eq!(unknown.to_string(), "3 mmol/L");
+    }
+}
@@ -0,0 +1,541 @@
+// Copyright (c) 2024 Ubique Innovation AG <https://www.ubique.ch>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use base64::Engine;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::models::{AttributeType, Cardinality, ConformancePolicy, EntryCode, Oca};
+
+/// A problem with the value of one attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// The conformance overlay marks the attribute mandatory, but it is absent.
+    MissingMandatory,
+    /// The JSON value does not fit the capture base type.
+    TypeMismatch {
+        expected: AttributeType,
+        actual: &'static str,
+    },
+    /// The value does not match the format overlay.
+    FormatMismatch { format: String },
+    /// The value is not one of the codes of the entry code overlay.
+    UnknownEntryCode { code: String },
+    /// The number of values is outside the cardinality overlay's range.
+    Cardinality { cardinality: String, count: usize },
+    /// An overlay entry for the attribute cannot be interpreted, e.g. an invalid
+    /// regular expression.
+    InvalidOverlay { reason: String },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Finding::MissingMandatory => write!(f, "mandatory value is missing"),
+            Finding::TypeMismatch { expected, actual } => {
+                write!(f, "expected {expected}, found {actual}")
+            }
+            Finding::FormatMismatch { format } => write!(f, "does not match format {format}"),
+            Finding::UnknownEntryCode { code } => write!(f, "{code} is not a valid entry code"),
+            Finding::Cardinality { cardinality, count } => {
+                write!(f, "{count} values, expected {cardinality}")
+            }
+            Finding::InvalidOverlay { reason } => write!(f, "invalid overlay: {reason}"),
+        }
+    }
+}
+
+/// The findings of [`validate`] by attribute path, e.g. `address.city` for
+/// attributes of referenced capture bases or `phones[1]` for array elements.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    findings: BTreeMap<String, Vec<Finding>>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.findings.is_empty()
+    }
+    pub fn findings(&self) -> &BTreeMap<String, Vec<Finding>> {
+        &self.findings
+    }
+    pub fn findings_for(&self, attribute: &str) -> &[Finding] {
+        self.findings
+            .get(attribute)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+    fn add(&mut self, path: &str, finding: Finding) {
+        self.findings
+            .entry(path.to_string())
+            .or_default()
+            .push(finding);
+    }
+}
+
+/// Checks credential `data` against the capture base and the conformance,
+/// format, entry code and cardinality overlays of `oca`.
+///
+/// Like the renderer, the attribute mapping overlay is applied first if there
+/// is one. Attributes referring to other capture bases are validated against
+/// those, using the referenced bundle's overlays.
+pub fn validate(oca: &Oca, data: &Value) -> ValidationReport {
+    let mapped = oca
+        .attribute_mapping()
+        .map(|mapping| mapping.map_json(data))
+        .filter(|mapped| mapped.as_object().is_some_and(|o| !o.is_empty()));
+    let mut report = ValidationReport::default();
+    validate_bundle(
+        oca,
+        oca,
+        mapped.as_ref().unwrap_or(data),
+        "",
+        0,
+        &mut report,
+    );
+    report
+}
+
+// limits how deep capture bases referring to each other are followed
+const MAX_DEPTH: usize = 8;
+
+fn validate_bundle(
+    root: &Oca,
+    bundle: &Oca,
+    data: &Value,
+    prefix: &str,
+    depth: usize,
+    report: &mut ValidationReport,
+) {
+    for (attribute, attribute_type) in bundle.attributes() {
+        let path = format!("{prefix}{attribute}");
+        let value = data.get(attribute).filter(|value| !value.is_null());
+        let cardinality = bundle
+            .overlays()
+            .first_of::<Cardinality>()
+            .and_then(|cardinality| cardinality.attribute_cardinality().get(attribute));
+        if let Some(cardinality) = cardinality {
+            let count = match value {
+                None => 0,
+                Some(Value::Array(values)) => values.len(),
+                Some(_) => 1,
+            };
+            match parse_cardinality(cardinality) {
+                Some((min, max)) if count < min || max.is_some_and(|max| count > max) => report
+                    .add(
+                        &path,
+                        Finding::Cardinality {
+                            cardinality: cardinality.clone(),
+                            count,
+                        },
+                    ),
+                Some(_) => {}
+                None => report.add(
+                    &path,
+                    Finding::InvalidOverlay {
+                        reason: format!("invalid cardinality {cardinality}"),
+                    },
+                ),
+            }
+        }
+        let Some(value) = value else {
+            if bundle.conformance_for(attribute) == Some(ConformancePolicy::M) {
+                report.add(&path, Finding::MissingMandatory);
+            }
+            continue;
+        };
+        let checker = AttributeChecker {
+            root,
+            bundle,
+            attribute,
+            format: bundle.format_for(attribute).map(Format::new),
+            depth,
+        };
+        checker.check(attribute_type, value, &path, report);
+    }
+}
+
+struct AttributeChecker<'a> {
+    root: &'a Oca,
+    bundle: &'a Oca,
+    attribute: &'a str,
+    // compiled once for all elements of an array
+    format: Option<Format<'a>>,
+    depth: usize,
+}
+
+impl AttributeChecker<'_> {
+    fn check(
+        &self,
+        attribute_type: &AttributeType,
+        value: &Value,
+        path: &str,
+        report: &mut ValidationReport,
+    ) {
+        let mismatch = || Finding::TypeMismatch {
+            expected: attribute_type.clone(),
+            actual: json_type(value),
+        };
+        match (attribute_type, value) {
+            (AttributeType::Array(inner), Value::Array(values)) => {
+                for (index, element) in values.iter().enumerate() {
+                    self.check(inner, element, &format!("{path}[{index}]"), report);
+                }
+            }
+            (AttributeType::Reference(digest), Value::Object(_)) => {
+                match self.root.bundle(digest) {
+                    Some(nested) if self.depth < MAX_DEPTH => validate_bundle(
+                        self.root,
+                        nested,
+                        value,
+                        &format!("{path}."),
+                        self.depth + 1,
+                        report,
+                    ),
+                    Some(_) => {}
+                    None => report.add(
+                        path,
+                        Finding::InvalidOverlay {
+                            reason: format!("capture base {digest} is missing"),
+                        },
+                    ),
+                }
+            }
+            (AttributeType::Text, Value::String(text)) => {
+                self.check_entry_code(text, path, report);
+                self.check_format(text, path, report);
+            }
+            (AttributeType::Numeric, Value::Number(number)) => {
+                self.check_entry_code(&number.to_string(), path, report);
+                self.check_format(&number.to_string(), path, report);
+            }
+            (AttributeType::DateTime, Value::String(value))
+            | (AttributeType::Binary, Value::String(value)) => {
+                self.check_format(value, path, report);
+            }
+            (AttributeType::Boolean, Value::Bool(_)) | (AttributeType::Other(_), _) => {}
+            _ => report.add(path, mismatch()),
+        }
+    }
+
+    fn check_format(&self, value: &str, path: &str, report: &mut ValidationReport) {
+        if let Some(format) = &self.format {
+            if let Err(finding) = format.check(value) {
+                report.add(path, finding);
+            }
+        }
+    }
+
+    fn check_entry_code(&self, code: &str, path: &str, report: &mut ValidationReport) {
+        let codes = self
+            .bundle
+            .overlays()
+            .first_of::<EntryCode>()
+            .and_then(|entry_code| entry_code.codes_for(self.attribute));
+        if codes.is_some_and(|codes| !codes.iter().any(|c| c == code)) {
+            report.add(
+                path,
+                Finding::UnknownEntryCode {
+                    code: code.to_string(),
+                },
+            );
+        }
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// The kinds of format overlay entries, told apart by their shape.
+enum Format<'a> {
+    /// A strftime pattern such as `%Y%m%d`.
+    Date(&'a str),
+    /// A media type such as `image/png`, for base64 encoded values.
+    MediaType(&'a str),
+    /// A regular expression the whole value must match.
+    Pattern(&'a str, Result<Regex, regex::Error>),
+}
+
+impl<'a> Format<'a> {
+    fn new(format: &'a str) -> Self {
+        if format.contains('%') {
+            Format::Date(format)
+        } else if is_media_type(format) {
+            Format::MediaType(format)
+        } else {
+            Format::Pattern(format, Regex::new(&format!("^(?:{format})$")))
+        }
+    }
+
+    fn check(&self, value: &str) -> Result<(), Finding> {
+        let (format, matches) = match self {
+            Format::Date(format) => (format, matches_date_format(value, format)),
+            Format::MediaType(format) => (format, matches_media_type(value, format)),
+            Format::Pattern(format, Ok(pattern)) => (format, pattern.is_match(value)),
+            Format::Pattern(format, Err(e)) => {
+                return Err(Finding::InvalidOverlay {
+                    reason: format!("invalid pattern {format}: {e}"),
+                })
+            }
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(Finding::FormatMismatch {
+                format: format.to_string(),
+            })
+        }
+    }
+}
+
+/// Whether `format` is a `type/subtype` media type rather than a pattern.
+fn is_media_type(format: &str) -> bool {
+    let token = |token: &str| {
+        !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    format
+        .split_once('/')
+        .is_some_and(|(kind, subtype)| token(kind) && token(subtype))
+}
+
+/// Date formats are strftime patterns such as `%Y%m%d`, for dates, times or both.
+fn matches_date_format(date: &str, format: &str) -> bool {
+    NaiveDateTime::parse_from_str(date, format).is_ok()
+        || NaiveDate::parse_from_str(date, format).is_ok()
+        || NaiveTime::parse_from_str(date, format).is_ok()
+}
+
+/// Binary values are base64, optionally as a data URL. Data URLs must declare
+/// the media type of the format overlay, plain base64 must decode and, for the
+/// common image and document types, start with the matching signature.
+fn matches_media_type(binary: &str, media_type: &str) -> bool {
+    let data = match binary.strip_prefix("data:") {
+        Some(url) => {
+            let Some((declared, data)) = url.split_once(";base64,") else {
+                return false;
+            };
+            if !declared.eq_ignore_ascii_case(media_type) {
+                return false;
+            }
+            data
+        }
+        None => binary,
+    };
+    let data = data.trim();
+    let Ok(bytes) = base64::prelude::BASE64_STANDARD
+        .decode(data)
+        .or_else(|_| base64::prelude::BASE64_STANDARD_NO_PAD.decode(data))
+        .or_else(|_| base64::prelude::BASE64_URL_SAFE.decode(data))
+        .or_else(|_| base64::prelude::BASE64_URL_SAFE_NO_PAD.decode(data))
+    else {
+        return false;
+    };
+    let signature: &[u8] = match media_type.to_ascii_lowercase().as_str() {
+        "image/png" => b"\x89PNG\r\n\x1a\n",
+        "image/jpeg" | "image/jpg" => b"\xFF\xD8\xFF",
+        "image/gif" => b"GIF8",
+        "application/pdf" => b"%PDF-",
+        _ => b"",
+    };
+    bytes.starts_with(signature)
+}
+
+/// Parses `n`, `n-`, `-m` and `n-m` into the minimum and optional maximum count.
+fn parse_cardinality(cardinality: &str) -> Option<(usize, Option<usize>)> {
+    let bound = |bound: &str| bound.trim().parse::<usize>();
+    match cardinality.split_once('-') {
+        None => {
+            let exact = bound(cardinality).ok()?;
+            Some((exact, Some(exact)))
+        }
+        Some((min, max)) => {
+            let min = if min.trim().is_empty() {
+                0
+            } else {
+                bound(min).ok()?
+            };
+            let max = if max.trim().is_empty() {
+                None
+            } else {
+                Some(bound(max).ok()?)
+            };
+            Some((min, max))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{validate, Finding};
+    use crate::{
+        builder::OcaBuilder,
+        models::{AttributeType, ConformancePolicy},
+    };
+
+    #[test]
+    fn findings_per_attribute() {
+        let oca = OcaBuilder::new()
+            .add_attribute("givenName", "Text")
+            .add_attribute("height", "Numeric")
+            .add_attribute("dateOfBirth", "DateTime")
+            .add_attribute("sex", "Text")
+            .add_attribute("nationalities", "Array[Text]")
+            .add_attribute("portrait", "Binary")
+            .add_conformance("givenName", ConformancePolicy::M)
+            .add_format("dateOfBirth", "%Y%m%d")
+            .add_format("height", "[0-9]{2,3}")
+            .add_format("portrait", "image/png")
+            .add_entry_codes("sex", &["F", "M"])
+            .add_cardinality("nationalities", "1-2")
+            .build()
+            .unwrap();
+
+        let valid = json!({
+            "givenName": "Erika",
+            "height": 172,
+            "dateOfBirth": "19640812",
+            "sex": "F",
+            "nationalities": ["CH"],
+            "portrait": "data:image/png;base64,iVBORw0KGgo="
+        });
+        let report = validate(&oca, &valid);
+        assert!(report.is_valid(), "{report:?}");
+
+        let invalid = json!({
+            "height": "172",
+            "dateOfBirth": "12.08.1964",
+            "sex": "X",
+            "nationalities": ["CH", "DE", 3],
+            "portrait": "data:image/jpeg;base64,/9j/"
+        });
+        let report = validate(&oca, &invalid);
+        assert_eq!(
+            report.findings_for("givenName"),
+            [Finding::MissingMandatory]
+        );
+        assert_eq!(
+            report.findings_for("height"),
+            [Finding::TypeMismatch {
+                expected: AttributeType::Numeric,
+                actual: "string"
+            }]
+        );
+        assert!(matches!(
+            report.findings_for("dateOfBirth"),
+            [Finding::FormatMismatch { .. }]
+        ));
+        assert!(matches!(
+            report.findings_for("sex"),
+            [Finding::UnknownEntryCode { code }] if code == "X"
+        ));
+        assert!(matches!(
+            report.findings_for("nationalities"),
+            [Finding::Cardinality { count: 3, .. }]
+        ));
+        assert!(matches!(
+            report.findings_for("nationalities[2]"),
+            [Finding::TypeMismatch { .. }]
+        ));
+        assert!(matches!(
+            report.findings_for("portrait"),
+            [Finding::FormatMismatch { .. }]
+        ));
+    }
+
+    #[test]
+    fn format_shapes_and_booleans() {
+        let oca = OcaBuilder::new()
+            .add_attribute("issued", "Text")
+            .add_attribute("signature", "Text")
+            .add_attribute("expiry", "DateTime")
+            .add_attribute("codes", "Array[Text]")
+            .add_attribute("adult", "Boolean")
+            .add_format("issued", "%Y-%m-%d")
+            .add_format("signature", "image/png")
+            .add_format("expiry", "[0-9]{4}")
+            .add_format("codes", "[A-Z")
+            .build()
+            .unwrap();
+        let report = validate(
+            &oca,
+            &json!({
+                "issued": "2024-01-31",
+                "signature": "iVBORw0KGgo=",
+                "expiry": "2030",
+                "adult": true
+            }),
+        );
+        assert!(report.is_valid(), "{report:?}");
+
+        let report = validate(
+            &oca,
+            &json!({
+                "issued": "31.01.2024",
+                "signature": "/9j/",
+                "expiry": "2030-01-01",
+                "codes": ["A", "B"],
+                "adult": "true"
+            }),
+        );
+        for attribute in ["issued", "signature", "expiry"] {
+            assert!(matches!(
+                report.findings_for(attribute),
+                [Finding::FormatMismatch { .. }]
+            ));
+        }
+        assert!(matches!(
+            report.findings_for("codes[1]"),
+            [Finding::InvalidOverlay { .. }]
+        ));
+        assert_eq!(
+            report.findings_for("adult"),
+            [Finding::TypeMismatch {
+                expected: AttributeType::Boolean,
+                actual: "string"
+            }]
+        );
+    }
+
+    #[test]
+    fn nested_and_mapped() {
+        let address = OcaBuilder::new()
+            .add_attribute("city", "Text")
+            .add_conformance("city", ConformancePolicy::M)
+            .build()
+            .unwrap();
+        let oca = OcaBuilder::new()
+            .add_attribute(
+                "address",
+                AttributeType::Reference(address.capture_base().digest().to_string()),
+            )
+            .add_mapping("address", "vc.home")
+            .add_dependency(address)
+            .build()
+            .unwrap();
+        let report = validate(&oca, &json!({ "vc": { "home": { "street": "Main" } } }));
+        assert_eq!(
+            report.findings_for("address.city"),
+            [Finding::MissingMandatory]
+        );
+        assert!(validate(&oca, &json!({ "vc": { "home": { "city": "Bern" } } })).is_valid());
+    }
+}